@@ -0,0 +1,251 @@
+use std::{
+    fs::{self, DirBuilder, OpenOptions},
+    io::{self, Write},
+    os::unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt},
+    path::{Path, PathBuf},
+    thread::sleep,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use nix::unistd::{Uid, User};
+use tracing::{debug, warn};
+
+/// The cookie context used when none is specified, as in the reference implementation.
+pub const DEFAULT_CONTEXT: &str = "org_freedesktop_general";
+
+// Timeouts from the reference implementation. Cookies are rotated after `NEW_COOKIE_TIMEOUT` but
+// remain valid for a while longer, so that clients that just read a cookie can still use it.
+const NEW_COOKIE_TIMEOUT: u64 = 5 * 60;
+const EXPIRE_COOKIES_TIMEOUT: u64 = NEW_COOKIE_TIMEOUT + 2 * 60;
+const MAX_TIME_TRAVEL: u64 = 5 * 60;
+
+const MAX_LOCK_ATTEMPTS: u32 = 32;
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(250);
+
+/// A secret cookie in a keyring.
+#[derive(Clone, Debug)]
+pub struct Cookie {
+    pub id: u32,
+    created: u64,
+    pub value: String,
+}
+
+/// A keyring of cookies, shared by all processes of a user through `~/.dbus-keyrings`.
+///
+/// All methods do blocking I/O.
+#[derive(Debug)]
+pub struct Keyring {
+    dir: PathBuf,
+    context: String,
+}
+
+impl Keyring {
+    /// The keyring of the current (effective) user for the given `context`.
+    ///
+    /// The home directory is looked up in the user database, not taken from `$HOME`.
+    pub fn for_current_user(context: &str) -> Result<Self> {
+        let uid = Uid::effective();
+        let user = User::from_uid(uid)?.ok_or_else(|| anyhow!("No user with UID {uid}."))?;
+
+        Self::new(user.dir.join(".dbus-keyrings"), context)
+    }
+
+    /// The keyring for the given `context` in `dir`.
+    pub fn new(dir: PathBuf, context: &str) -> Result<Self> {
+        if context.is_empty()
+            || context
+                .chars()
+                .any(|c| c == '/' || c == '\\' || c == '.' || c.is_whitespace())
+        {
+            bail!("Invalid cookie context `{context}`.");
+        }
+
+        Ok(Self {
+            dir,
+            context: context.to_string(),
+        })
+    }
+
+    pub fn context(&self) -> &str {
+        &self.context
+    }
+
+    /// The most recent cookie, after getting rid of expired ones and creating a new one if needed.
+    pub fn newest_cookie(&self) -> Result<Cookie> {
+        self.ensure_dir()?;
+        let _lock = Lock::acquire(&self.dir.join(format!("{}.lock", self.context)))?;
+
+        let now = now();
+        let loaded = self.load()?;
+        let n_loaded = loaded.len();
+        let mut cookies: Vec<_> = loaded
+            .into_iter()
+            .filter(|c| c.created <= now + MAX_TIME_TRAVEL)
+            .filter(|c| now.saturating_sub(c.created) < EXPIRE_COOKIES_TIMEOUT)
+            .collect();
+        let mut changed = cookies.len() != n_loaded;
+
+        if !cookies
+            .iter()
+            .any(|c| now.saturating_sub(c.created) < NEW_COOKIE_TIMEOUT)
+        {
+            let mut id = random_u32()?;
+            while cookies.iter().any(|c| c.id == id) {
+                id = random_u32()?;
+            }
+            debug!("Adding cookie {id} to keyring `{}`.", self.context);
+            cookies.push(Cookie {
+                id,
+                created: now,
+                value: random_hex(24)?,
+            });
+            changed = true;
+        }
+
+        if changed {
+            self.save(&cookies)?;
+        }
+
+        cookies
+            .into_iter()
+            .max_by_key(|c| c.created)
+            .ok_or_else(|| anyhow!("No cookie in keyring `{}`.", self.context))
+    }
+
+    /// Create the keyring directory if needed, and make sure no one else has access to it.
+    fn ensure_dir(&self) -> Result<()> {
+        match DirBuilder::new().mode(0o700).create(&self.dir) {
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => (),
+            res => res.with_context(|| format!("Failed to create `{}`", self.dir.display()))?,
+        }
+
+        let metadata = fs::symlink_metadata(&self.dir)?;
+        if !metadata.is_dir() {
+            bail!("Keyring `{}` is not a directory.", self.dir.display());
+        }
+        if metadata.uid() != Uid::effective().as_raw() {
+            bail!("Keyring directory `{}` is not ours.", self.dir.display());
+        }
+        if metadata.mode() & 0o077 != 0 {
+            bail!(
+                "Keyring directory `{}` is accessible by other users (mode {:o}).",
+                self.dir.display(),
+                metadata.mode() & 0o777
+            );
+        }
+
+        Ok(())
+    }
+
+    fn load(&self) -> Result<Vec<Cookie>> {
+        let path = self.dir.join(&self.context);
+        let contents = match fs::read_to_string(&path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            res => res.with_context(|| format!("Failed to read `{}`", path.display()))?,
+        };
+
+        Ok(contents
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split_ascii_whitespace();
+                let id = fields.next()?.parse().ok()?;
+                let created = fields.next()?.parse().ok()?;
+                let value = fields.next()?;
+                if value.is_empty() || !value.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return None;
+                }
+
+                Some(Cookie {
+                    id,
+                    created,
+                    value: value.to_string(),
+                })
+            })
+            .collect())
+    }
+
+    fn save(&self, cookies: &[Cookie]) -> Result<()> {
+        let path = self.dir.join(&self.context);
+        let tmp_path = self.dir.join(format!("{}.tmp", self.context));
+        let contents: String = cookies
+            .iter()
+            .map(|c| format!("{} {} {}\n", c.id, c.created, c.value))
+            .collect();
+
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&tmp_path)
+            .with_context(|| format!("Failed to write `{}`", tmp_path.display()))?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, &path)
+            .with_context(|| format!("Failed to replace `{}`", path.display()))?;
+
+        Ok(())
+    }
+}
+
+/// Exclusive access to a keyring, through a lock file.
+struct Lock {
+    path: PathBuf,
+}
+
+impl Lock {
+    fn acquire(path: &Path) -> Result<Self> {
+        for _ in 0..MAX_LOCK_ATTEMPTS {
+            match Self::try_acquire(path) {
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => sleep(LOCK_RETRY_INTERVAL),
+                res => return res.with_context(|| format!("Failed to lock `{}`", path.display())),
+            }
+        }
+
+        // Whoever holds the lock has had plenty of time, so assume they died while holding it.
+        warn!("Removing stale keyring lock file `{}`.", path.display());
+        fs::remove_file(path)?;
+
+        Self::try_acquire(path).with_context(|| format!("Failed to lock `{}`", path.display()))
+    }
+
+    fn try_acquire(path: &Path) -> io::Result<Self> {
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(path)?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            warn!("Failed to remove `{}`: {e}", self.path.display());
+        }
+    }
+}
+
+/// `len` random bytes from the OS, as hex digits.
+pub(super) fn random_hex(len: usize) -> Result<String> {
+    let mut bytes = vec![0; len];
+    getrandom::fill(&mut bytes).context("Failed to get random bytes")?;
+
+    Ok(super::encode_hex(&bytes))
+}
+
+fn random_u32() -> Result<u32> {
+    getrandom::u32().context("Failed to get random bytes")
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
@@ -0,0 +1,66 @@
+use std::{io, mem, os::fd::BorrowedFd};
+
+use async_trait::async_trait;
+use zbus::{
+    connection::socket::{BoxedSplit, WriteHalf},
+    fdo::ConnectionCredentials,
+    Message,
+};
+
+/// Make the peer on the other end of `socket` appear to be the user with the given `uid`.
+///
+/// This only affects the credentials reported for the connection once it's established. The
+/// handshake is told about `uid` separately.
+pub(crate) fn override_uid(mut socket: BoxedSplit, uid: u32) -> BoxedSplit {
+    let write = mem::replace(socket.write_mut(), Box::new(Detached));
+    *socket.write_mut() = Box::new(Overridden { write, uid });
+
+    socket
+}
+
+/// A write half with the peer's user ID replaced.
+#[derive(Debug)]
+struct Overridden {
+    write: Box<dyn WriteHalf>,
+    uid: u32,
+}
+
+#[async_trait]
+impl WriteHalf for Overridden {
+    async fn send_message(&mut self, msg: &Message) -> zbus::Result<()> {
+        self.write.send_message(msg).await
+    }
+
+    async fn sendmsg(&mut self, buffer: &[u8], fds: &[BorrowedFd<'_>]) -> io::Result<usize> {
+        self.write.sendmsg(buffer, fds).await
+    }
+
+    async fn close(&mut self) -> io::Result<()> {
+        self.write.close().await
+    }
+
+    fn can_pass_unix_fd(&self) -> bool {
+        self.write.can_pass_unix_fd()
+    }
+
+    async fn peer_credentials(&mut self) -> io::Result<ConnectionCredentials> {
+        let actual = self.write.peer_credentials().await?;
+        let creds = ConnectionCredentials::default().set_unix_user_id(self.uid);
+
+        Ok(match actual.process_id() {
+            Some(pid) => creds.set_process_id(pid),
+            None => creds,
+        })
+    }
+}
+
+/// Stand-in for the write half while it's being wrapped. Never used for any I/O.
+#[derive(Debug)]
+struct Detached;
+
+#[async_trait]
+impl WriteHalf for Detached {
+    async fn close(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,394 @@
+mod cookie;
+pub use cookie::*;
+mod identity;
+pub(crate) use identity::override_uid;
+mod policy;
+pub use policy::*;
+
+use std::{fmt, path::Path, str::FromStr};
+
+use anyhow::{anyhow, bail, Context, Error, Result};
+use nix::unistd::{Uid, User};
+use sha1::{Digest, Sha1};
+use tracing::{debug, trace};
+use zbus::{address::Transport, connection::socket::BoxedSplit, OwnedGuid};
+
+/// Maximum length of a line in the authentication protocol, same as the reference implementation.
+const MAX_LINE_LEN: usize = 16 * 1024;
+
+/// The authentication mechanisms the bus supports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mechanism {
+    /// Authentication through the credentials of the peer process, passed by the kernel.
+    External,
+    /// Authentication by proving knowledge of a secret cookie that only the bus owner can read.
+    CookieSha1,
+    /// No authentication at all.
+    Anonymous,
+}
+
+impl Mechanism {
+    /// All the supported mechanisms, in order of preference.
+    pub const ALL: &'static [Mechanism] = &[
+        Mechanism::External,
+        Mechanism::CookieSha1,
+        Mechanism::Anonymous,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Mechanism::External => "EXTERNAL",
+            Mechanism::CookieSha1 => "DBUS_COOKIE_SHA1",
+            Mechanism::Anonymous => "ANONYMOUS",
+        }
+    }
+
+    /// Whether this mechanism can be used for peers connecting through `transport`.
+    pub fn supports(&self, transport: &Transport) -> bool {
+        match self {
+            // Credentials are only available on UNIX sockets.
+            Mechanism::External => matches!(transport, Transport::Unix(_)),
+            Mechanism::CookieSha1 | Mechanism::Anonymous => true,
+        }
+    }
+}
+
+impl fmt::Display for Mechanism {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Mechanism {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Mechanism::ALL
+            .iter()
+            .find(|m| m.as_str() == s)
+            .copied()
+            .ok_or_else(|| anyhow!("Unsupported authentication mechanism `{s}`."))
+    }
+}
+
+/// The outcome of a successful authentication handshake.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Authenticated {
+    /// The mechanism the peer authenticated with.
+    pub mechanism: Mechanism,
+    /// The user the peer authenticated as, unless it's anonymous.
+    pub uid: Option<u32>,
+}
+
+/// Perform the server side of the authentication handshake on `socket`.
+///
+/// Only `mechanisms` are offered to the client. If `uid` is given, a peer that authenticates (other
+/// than anonymously) is taken to be that user, rather than whoever it proved to be. The cookies of
+/// `DBUS_COOKIE_SHA1` are kept in `keyring_dir`, `~/.dbus-keyrings` of the effective user by
+/// default. On success, `socket` is left right after the `BEGIN` command, ready to be used with
+/// [`zbus::connection::Builder::authenticated_socket`].
+pub async fn handshake(
+    socket: &mut BoxedSplit,
+    guid: &OwnedGuid,
+    mechanisms: &[Mechanism],
+    uid: Option<u32>,
+    keyring_dir: Option<&Path>,
+) -> Result<Authenticated> {
+    Handshake {
+        socket,
+        guid,
+        mechanisms,
+        uid,
+        keyring_dir,
+        first_line: true,
+    }
+    .perform()
+    .await
+}
+
+struct Handshake<'h> {
+    socket: &'h mut BoxedSplit,
+    guid: &'h OwnedGuid,
+    mechanisms: &'h [Mechanism],
+    uid: Option<u32>,
+    keyring_dir: Option<&'h Path>,
+    first_line: bool,
+}
+
+impl Handshake<'_> {
+    async fn perform(mut self) -> Result<Authenticated> {
+        let mut authenticated = None;
+        loop {
+            let line = self.read_line().await?;
+            let mut words = line.split_ascii_whitespace();
+            match (words.next().unwrap_or_default(), authenticated) {
+                ("AUTH", None) => {
+                    let mechanism = words.next();
+                    let response = words.next();
+                    authenticated = self.auth(mechanism, response).await?;
+                }
+                ("BEGIN", Some(authenticated)) => {
+                    debug!("Peer authenticated: {authenticated:?}");
+
+                    return Ok(authenticated);
+                }
+                ("BEGIN", None) => bail!("Peer sent `BEGIN` without authenticating."),
+                ("NEGOTIATE_UNIX_FD", Some(_)) => {
+                    if self.socket.read().can_pass_unix_fd() {
+                        self.write_line("AGREE_UNIX_FD").await?;
+                    } else {
+                        self.write_line("ERROR \"File descriptor passing not supported\"")
+                            .await?;
+                    }
+                }
+                ("CANCEL" | "ERROR", _) => {
+                    authenticated = None;
+                    self.reject().await?;
+                }
+                _ => {
+                    self.write_line("ERROR \"Unknown or misplaced command\"")
+                        .await?
+                }
+            }
+        }
+    }
+
+    /// Handle an `AUTH` command, returning the outcome if the peer got authenticated.
+    async fn auth(
+        &mut self,
+        mechanism: Option<&str>,
+        response: Option<&str>,
+    ) -> Result<Option<Authenticated>> {
+        let mechanism = match mechanism
+            .and_then(|m| m.parse::<Mechanism>().ok())
+            .filter(|m| self.mechanisms.contains(m))
+        {
+            Some(mechanism) => mechanism,
+            None => {
+                self.reject().await?;
+
+                return Ok(None);
+            }
+        };
+        let response = match response.map(decode_hex).transpose() {
+            Ok(response) => response,
+            Err(e) => {
+                debug!("Invalid initial response: {e}");
+                self.reject().await?;
+
+                return Ok(None);
+            }
+        };
+
+        let uid = match mechanism {
+            Mechanism::External => {
+                let id = match response {
+                    Some(id) => Some(id),
+                    None => {
+                        self.write_line("DATA").await?;
+
+                        self.read_data().await?
+                    }
+                };
+                match id {
+                    Some(id) => self.check_external(&id).await?.map(Some),
+                    None => None,
+                }
+            }
+            Mechanism::CookieSha1 => match response {
+                Some(username) => self.cookie_sha1(&username).await?.map(Some),
+                None => None,
+            },
+            Mechanism::Anonymous => Some(None),
+        };
+
+        match uid {
+            Some(uid) => {
+                // Whoever proved who they are is then taken to be the user they were vouched for
+                // as.
+                let uid = uid.map(|uid| self.uid.unwrap_or(uid));
+                self.write_line(&format!("OK {}", self.guid)).await?;
+
+                Ok(Some(Authenticated { mechanism, uid }))
+            }
+            None => {
+                self.reject().await?;
+
+                Ok(None)
+            }
+        }
+    }
+
+    /// Check the identity claimed by the peer against the credentials of its process.
+    ///
+    /// An empty identity means the peer wants to be whoever its credentials say. Returns the UID of
+    /// the peer on success.
+    async fn check_external(&mut self, id: &[u8]) -> Result<Option<u32>> {
+        let creds = self.socket.read_mut().peer_credentials().await?;
+        let uid = match creds.unix_user_id() {
+            Some(uid) => uid,
+            None => {
+                debug!("No credentials for the peer.");
+
+                return Ok(None);
+            }
+        };
+        if id.is_empty() {
+            return Ok(Some(uid));
+        }
+
+        let claimed = std::str::from_utf8(id)
+            .ok()
+            .and_then(|id| id.parse::<u32>().ok());
+        trace!("Peer with UID {uid} claims to be {claimed:?}.");
+
+        Ok((claimed == Some(uid)).then_some(uid))
+    }
+
+    /// Challenge the peer to prove it can read our cookies.
+    ///
+    /// Only the user the bus runs as can be authenticated this way. Returns their UID on success.
+    async fn cookie_sha1(&mut self, username: &[u8]) -> Result<Option<u32>> {
+        let uid = Uid::effective();
+        let username = String::from_utf8_lossy(username);
+        let is_us = match username.parse::<u32>() {
+            Ok(claimed) => claimed == uid.as_raw(),
+            Err(_) => User::from_name(&username)?.is_some_and(|user| user.uid == uid),
+        };
+        if !is_us {
+            debug!("Peer claims to be `{username}`, who we can't authenticate.");
+
+            return Ok(None);
+        }
+
+        let keyring_dir = self.keyring_dir.map(Path::to_path_buf);
+        let (context, cookie) = tokio::task::spawn_blocking(|| {
+            let keyring = match keyring_dir {
+                Some(dir) => Keyring::new(dir, DEFAULT_CONTEXT)?,
+                None => Keyring::for_current_user(DEFAULT_CONTEXT)?,
+            };
+            let cookie = keyring.newest_cookie()?;
+
+            Ok::<_, Error>((keyring.context().to_string(), cookie))
+        })
+        .await??;
+        let server_challenge = random_hex(24)?;
+        let challenge = format!("{context} {} {server_challenge}", cookie.id);
+        self.write_line(&format!("DATA {}", encode_hex(challenge.as_bytes())))
+            .await?;
+
+        let response = match self.read_data().await? {
+            Some(response) => String::from_utf8_lossy(&response).into_owned(),
+            None => return Ok(None),
+        };
+        let (client_challenge, digest) = match response.split_once(' ') {
+            Some(fields) => fields,
+            None => return Ok(None),
+        };
+        let expected = encode_hex(&Sha1::digest(format!(
+            "{server_challenge}:{client_challenge}:{}",
+            cookie.value
+        )));
+
+        Ok(constant_time_eq(expected.as_bytes(), digest.as_bytes()).then_some(uid.as_raw()))
+    }
+
+    /// Read the response to a `DATA` challenge.
+    ///
+    /// Returns `None` if the peer doesn't send any data back.
+    async fn read_data(&mut self) -> Result<Option<Vec<u8>>> {
+        let line = self.read_line().await?;
+        let mut words = line.split_ascii_whitespace();
+        if words.next() != Some("DATA") {
+            return Ok(None);
+        }
+
+        match words.next().map(decode_hex).transpose() {
+            Ok(data) => Ok(Some(data.unwrap_or_default())),
+            Err(e) => {
+                debug!("Invalid data: {e}");
+
+                Ok(None)
+            }
+        }
+    }
+
+    async fn reject(&mut self) -> Result<()> {
+        let mechanisms = self
+            .mechanisms
+            .iter()
+            .map(Mechanism::as_str)
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        self.write_line(&format!("REJECTED {mechanisms}")).await
+    }
+
+    // Lines are read a byte at a time so that we don't consume anything the peer sends right after
+    // `BEGIN`. That belongs to the first message.
+    async fn read_line(&mut self) -> Result<String> {
+        let mut line = Vec::new();
+        while !line.ends_with(b"\r\n") {
+            if line.len() >= MAX_LINE_LEN {
+                bail!("Peer sent a line that is too long.");
+            }
+
+            let mut byte = [0];
+            let (read, _) = self.socket.read_mut().recvmsg(&mut byte).await?;
+            if read == 0 {
+                bail!("Unexpected EOF during handshake.");
+            }
+            line.push(byte[0]);
+        }
+        line.truncate(line.len() - 2);
+
+        if self.first_line {
+            self.first_line = false;
+            match line.first() {
+                Some(b'\0') => {
+                    line.remove(0);
+                }
+                _ => bail!("First byte from peer is not NUL."),
+            }
+        }
+
+        let line = String::from_utf8(line).context("Peer sent invalid UTF-8")?;
+        trace!("Received `{line}`.");
+
+        Ok(line)
+    }
+
+    async fn write_line(&mut self, line: &str) -> Result<()> {
+        trace!("Sending `{line}`.");
+        let mut bytes = format!("{line}\r\n").into_bytes();
+        while !bytes.is_empty() {
+            let written = self.socket.write_mut().sendmsg(&bytes, &[]).await?;
+            bytes.drain(..written);
+        }
+
+        Ok(())
+    }
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+fn decode_hex(hex: &str) -> Result<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        bail!("Odd number of hex digits.");
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                .ok_or_else(|| anyhow!("Invalid hex digits."))
+        })
+        .collect()
+}
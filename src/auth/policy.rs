@@ -0,0 +1,92 @@
+use nix::unistd::{Group, Uid, User};
+use tracing::debug;
+
+use crate::config::{Access, ConnectOperation, Operation, Policy};
+
+/// Who may connect to the bus, once authenticated.
+///
+/// Only the bus owner is allowed by default. `<allow>` and `<deny>` rules with `user` or `group`
+/// attributes in the default and mandatory policies change that, the last matching rule winning.
+/// As in the reference implementation, per-user and per-group policies are not considered.
+#[derive(Clone, Debug)]
+pub struct ConnectPolicy {
+    owner: Uid,
+    rules: Vec<(Access, ConnectOperation)>,
+}
+
+impl ConnectPolicy {
+    pub fn new(owner: Uid, policies: &[Policy]) -> Self {
+        let default_rules = policies.iter().filter_map(|policy| match policy {
+            Policy::DefaultContext(rules) => Some(rules),
+            _ => None,
+        });
+        let mandatory_rules = policies.iter().filter_map(|policy| match policy {
+            Policy::MandatoryContext(rules) => Some(rules),
+            _ => None,
+        });
+        let rules = default_rules
+            .chain(mandatory_rules)
+            .flatten()
+            .filter_map(|(access, operation)| match operation {
+                Operation::Connect(connect) => Some((access.clone(), connect.clone())),
+                _ => None,
+            })
+            .collect();
+
+        Self { owner, rules }
+    }
+
+    /// The user the bus runs as.
+    pub fn owner(&self) -> Uid {
+        self.owner
+    }
+
+    /// Whether the user with the given `uid` may connect.
+    pub fn allows(&self, uid: u32) -> bool {
+        let uid = Uid::from_raw(uid);
+        // Only look the user up if there are rules that need it.
+        let mut user = None;
+
+        self.rules
+            .iter()
+            .fold(uid == self.owner, |allowed, (access, connect)| {
+                let user = user.get_or_insert_with(|| User::from_uid(uid).ok().flatten());
+                if matches(connect, uid, user.as_ref()) {
+                    *access == Access::Allow
+                } else {
+                    allowed
+                }
+            })
+    }
+}
+
+fn matches(connect: &ConnectOperation, uid: Uid, user: Option<&User>) -> bool {
+    if let Some(name) = &connect.user {
+        let matched = name == "*"
+            || name.parse::<u32>().ok() == Some(uid.as_raw())
+            || user.is_some_and(|user| user.name == *name);
+        if !matched {
+            return false;
+        }
+    }
+
+    if let Some(name) = &connect.group {
+        if name == "*" {
+            return true;
+        }
+
+        let group = match Group::from_name(name) {
+            Ok(Some(group)) => group,
+            Ok(None) => return false,
+            Err(e) => {
+                debug!("Failed to look up group `{name}`: {e}");
+
+                return false;
+            }
+        };
+
+        return user.is_some_and(|user| user.gid == group.gid || group.mem.contains(&user.name));
+    }
+
+    true
+}
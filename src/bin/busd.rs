@@ -1,30 +1,91 @@
 extern crate busd;
 
-use std::{fs::File, io::Write, os::fd::FromRawFd, path::PathBuf};
+use std::{
+    collections::BTreeMap,
+    env::temp_dir,
+    ffi::OsString,
+    fs::File,
+    future::pending,
+    io::Write,
+    mem::ManuallyDrop,
+    os::{fd::FromRawFd, unix::process::ExitStatusExt},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
-use busd::{bus, config::Config};
+use busd::{
+    bus,
+    config::{Config, IpNetwork},
+    daemon::{daemonize, Daemonized, Readiness},
+    fdo,
+    sd_notify::{self, Notifier},
+    tracing_subscriber::Syslog,
+};
 
-use anyhow::Result;
-use clap::Parser;
-use tokio::{select, signal::unix::SignalKind};
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand};
+use nix::{
+    errno::Errno,
+    sys::signal::{kill, Signal},
+    unistd::{getppid, Pid},
+};
+use tokio::{select, signal::unix::SignalKind, task::JoinHandle, time::sleep};
 use tracing::{error, info, warn};
+use zbus::fdo::IntrospectableProxy;
+
+/// How long to wait for messages being sent to go out when shutting down.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often to check if the process we exit with is still around.
+const PROCESS_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The configuration used for session buses, unless told otherwise.
+const SESSION_CONFIG: &str = "/usr/share/dbus-1/session.conf";
 
 /// A simple D-Bus broker.
 #[derive(Parser, Debug)]
-#[clap(author, version, about, long_about = None)]
+#[clap(author, version, about, long_about = None, args_conflicts_with_subcommands = true)]
 struct Args {
+    #[clap(subcommand)]
+    command: Option<Command>,
+
     /// The address to listen on.
     /// Takes precedence over any `<listen>` element in the configuration file.
     #[clap(short = 'a', long, value_parser)]
     address: Option<String>,
 
     /// Use the given configuration file.
-    #[clap(long)]
+    #[clap(long, visible_alias = "config-file", conflicts_with_all = ["session", "system"])]
     config: Option<PathBuf>,
 
-    /// Print the address of the message bus to standard output.
+    /// Run in the background, regardless of the `<fork>` element in the configuration file.
+    ///
+    /// The address is printed and the readiness notification is sent only once the daemon is
+    /// listening.
+    #[clap(long, conflicts_with = "nofork")]
+    fork: bool,
+
+    /// Stay in the foreground, regardless of the `<fork>` element in the configuration file.
+    #[clap(long)]
+    nofork: bool,
+
+    /// Don't write a PID file, regardless of the `<pidfile>` element in the configuration file.
     #[clap(long)]
-    print_address: bool,
+    nopidfile: bool,
+
+    /// Print the introspection data of the message bus driver and exit.
+    #[clap(long)]
+    introspect: bool,
+
+    /// Print the address of the message bus to standard output, or to the given file descriptor.
+    #[clap(long, value_name = "FD", num_args = 0..=1, default_missing_value = "1")]
+    print_address: Option<i32>,
+
+    /// Print the process ID of the message bus to standard output, or to the given file
+    /// descriptor.
+    #[clap(long, value_name = "FD", num_args = 0..=1, default_missing_value = "1")]
+    print_pid: Option<i32>,
 
     /// File descriptor to which readiness notifications are sent.
     ///
@@ -39,64 +100,515 @@ struct Args {
 
     /// Equivalent to `--config /usr/share/dbus-1/session.conf`.
     /// This is the default if `--config` and `--system` are unspecified.
-    #[clap(long)]
+    #[clap(long, conflicts_with = "system")]
     session: bool,
 
     /// Equivalent to `--config /usr/share/dbus-1/system.conf`.
     #[clap(long)]
     system: bool,
+
+    /// Log to the system log as well, regardless of the `<syslog>` element in the configuration
+    /// file.
+    #[clap(long, conflicts_with_all = ["syslog_only", "nosyslog"])]
+    syslog: bool,
+
+    /// Only log to the system log.
+    #[clap(long, conflicts_with = "nosyslog")]
+    syslog_only: bool,
+
+    /// Don't log to the system log, regardless of the `<syslog>` element in the configuration
+    /// file.
+    #[clap(long)]
+    nosyslog: bool,
+
+    /// Only accept TCP connections from clients in this network, given in CIDR notation (e.g.
+    /// `10.0.0.0/8`). Can be given multiple times.
+    ///
+    /// By default, clients can connect from anywhere.
+    #[clap(long, value_name = "CIDR")]
+    tcp_allow: Vec<IpNetwork>,
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    busd::tracing_subscriber::init();
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run a program with its own session bus, as `dbus-run-session` does.
+    ///
+    /// The bus listens in the temporary directory and its address is given to the program in
+    /// `DBUS_SESSION_BUS_ADDRESS`. Once the program exits, so does the bus, with the exit status
+    /// of the program. SIGINT, SIGTERM and SIGHUP are passed on to the program.
+    RunSession(RunSessionArgs),
+
+    /// Start a session bus in the background, as `dbus-launch` does.
+    ///
+    /// Once the bus is listening, its address and process ID are printed as
+    /// `DBUS_SESSION_BUS_ADDRESS` and `DBUS_SESSION_BUS_PID`, in a form shell scripts can `eval`.
+    Launch(LaunchArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct RunSessionArgs {
+    /// Use the given configuration file rather than the standard session bus configuration, if
+    /// any. Any `<listen>` element in it is ignored.
+    #[clap(long, visible_alias = "config")]
+    config_file: Option<PathBuf>,
+
+    /// The program to run, followed by its arguments.
+    #[clap(required = true, trailing_var_arg = true, value_name = "PROGRAM")]
+    command: Vec<OsString>,
+}
+
+#[derive(clap::Args, Debug)]
+struct LaunchArgs {
+    /// Use the given configuration file rather than the standard session bus configuration, if
+    /// any.
+    #[clap(long, visible_alias = "config")]
+    config_file: Option<PathBuf>,
+
+    /// Print Bourne shell commands setting the variables.
+    #[clap(long, short = 's', conflicts_with_all = ["csh_syntax", "binary_syntax"])]
+    sh_syntax: bool,
+
+    /// Print C shell commands setting the variables.
+    #[clap(long, short = 'c', conflicts_with = "binary_syntax")]
+    csh_syntax: bool,
+
+    /// Print the address, terminated by a NUL byte, followed by the process ID as a native `pid_t`
+    /// and a native `long` that is always 0, as `dbus-launch` does for the X window ID.
+    #[clap(long)]
+    binary_syntax: bool,
+
+    /// Exit the bus once the process that ran this command (e.g. the shell of the session) exits.
+    #[clap(long)]
+    exit_with_session: bool,
+}
 
+fn main() -> Result<()> {
     let args = Args::parse();
 
+    match &args.command {
+        Some(Command::RunSession(run_session_args)) => {
+            busd::tracing_subscriber::init();
+            let code = tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .build()?
+                .block_on(run_session(run_session_args))?;
+
+            std::process::exit(code);
+        }
+        Some(Command::Launch(launch_args)) => return launch(&args, launch_args),
+        None => (),
+    }
+
+    if args.introspect {
+        return tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?
+            .block_on(introspect());
+    }
+
     let config_path = if args.system {
         PathBuf::from("/usr/share/dbus-1/system.conf")
-    } else if let Some(config_path) = args.config {
-        config_path
+    } else if let Some(config_path) = &args.config {
+        config_path.clone()
     } else {
-        PathBuf::from("/usr/share/dbus-1/session.conf")
+        PathBuf::from(SESSION_CONFIG)
     };
-    info!("reading configuration file {} ...", config_path.display());
-    let config = Config::read_file(&config_path)?;
+    let mut config = Config::read_file(&config_path)?;
 
-    let address = if let Some(address) = args.address {
-        Some(address)
+    let syslog = if args.nosyslog {
+        Syslog::Off
+    } else if args.syslog_only {
+        Syslog::Only
+    } else if args.syslog || config.syslog {
+        Syslog::On
     } else {
-        config.listen.map(|address| format!("{address}"))
+        Syslog::Off
     };
+    busd::tracing_subscriber::init_with_syslog(syslog);
+    info!("read configuration file {}", config_path.display());
 
-    let mut bus = bus::Bus::for_address(address.as_deref()).await?;
-
-    if let Some(fd) = args.ready_fd {
-        // SAFETY: We don't have any way to know if the fd is valid or not. The parent process is
-        // responsible for passing a valid fd.
-        let mut ready_file = unsafe { File::from_raw_fd(fd) };
-        ready_file.write_all(b"READY=1\n")?;
+    if let Some(address) = &args.address {
+        config.listen = Some(bus::parse_address(address)?);
+    }
+    config.tcp_allowed_clients = args.tcp_allow.clone();
+    if args.nopidfile {
+        config.pidfile = None;
     }
 
-    if args.print_address {
-        println!("{}", bus.address());
+    let fork = !args.nofork && (args.fork || config.fork);
+    let readiness = if fork {
+        // This has to happen before the runtime spawns any threads.
+        let announce_fds: Vec<_> = [args.ready_fd, args.print_address, args.print_pid]
+            .into_iter()
+            .flatten()
+            .collect();
+        match daemonize(config.keep_umask, &announce_fds)? {
+            Daemonized::Parent { pid, address } => {
+                return announce(&args, &address, pid.as_raw() as u32)
+            }
+            Daemonized::Daemon(readiness) => Some(readiness),
+        }
+    } else {
+        None
+    };
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?
+        .block_on(run(&args, Some(&config_path), config, readiness, None))
+}
+
+/// Run the bus until we're told to stop, or until the process `exit_with` exits.
+async fn run(
+    args: &Args,
+    config_path: Option<&Path>,
+    config: Config,
+    readiness: Option<Readiness>,
+    exit_with: Option<Pid>,
+) -> Result<()> {
+    let mut bus = match bus::Bus::for_config(config).await {
+        Ok(bus) => bus,
+        Err(e) => {
+            if let Some(readiness) = readiness {
+                readiness.failed(&e);
+            }
+
+            return Err(e);
+        }
+    };
+    if let Some(config_path) = config_path {
+        bus.set_config_file(config_path);
+        #[cfg(target_os = "linux")]
+        if let Err(e) = bus.watch_config_file() {
+            warn!("Configuration changes will only apply on SIGHUP: {e:#}");
+        }
     }
+    let address = bus.address().map(ToString::to_string).unwrap_or_default();
 
+    // Before announcing ourselves, so that whoever gets our PID can signal us right away.
     let mut sig_int = tokio::signal::unix::signal(SignalKind::interrupt())?;
+    let mut sig_term = tokio::signal::unix::signal(SignalKind::terminate())?;
+    let mut sig_hup = tokio::signal::unix::signal(SignalKind::hangup())?;
 
-    select! {
-        _ = sig_int.recv() => {
-            info!("Received SIGINT, shutting down..");
+    match readiness {
+        Some(readiness) => readiness.ready(&address)?,
+        None => announce(args, &address, std::process::id())?,
+    }
+    let notifier = match Notifier::from_env() {
+        Ok(notifier) => notifier.map(Arc::new),
+        Err(e) => {
+            warn!("Failed to connect to the service manager: {e}");
+
+            None
+        }
+    };
+    let keep_alive = match &notifier {
+        Some(notifier) => Some(start_keep_alive(&bus, notifier.clone()).await?),
+        None => None,
+    };
+    let exited = async {
+        match exit_with {
+            Some(pid) => process_exit(pid).await,
+            None => pending().await,
         }
-        res = bus.run() => match res {
-            Ok(()) => warn!("Bus stopped, shutting down.."),
-            Err(e) => error!("Bus stopped with an error: {}", e),
+    };
+    tokio::pin!(exited);
+
+    loop {
+        select! {
+            _ = sig_int.recv() => {
+                info!("Received SIGINT, shutting down..");
+
+                break;
+            }
+            _ = sig_term.recv() => {
+                info!("Received SIGTERM, shutting down..");
+
+                break;
+            }
+            _ = sig_hup.recv() => info!("Received SIGHUP, reloading configuration.."),
+            _ = &mut exited => {
+                info!("The session ended, shutting down..");
+
+                break;
+            }
+            res = bus.run() => {
+                match res {
+                    Ok(()) => warn!("Bus stopped, shutting down.."),
+                    Err(e) => error!("Bus stopped with an error: {}", e),
+                }
+
+                break;
+            }
+        }
+
+        if let Err(e) = bus.reload_config().await {
+            error!("{e:#}");
         }
     }
 
-    if let Err(e) = bus.cleanup().await {
+    if let Some(keep_alive) = keep_alive {
+        keep_alive.abort();
+    }
+    if let Some(notifier) = notifier {
+        if let Err(e) = notifier.stopping() {
+            warn!("Failed to notify the service manager: {e}");
+        }
+    }
+    if let Err(e) = bus.shutdown(SHUTDOWN_TIMEOUT).await {
+        error!("Failed to clean up: {}", e);
+    }
+
+    Ok(())
+}
+
+/// Run a program on a private session bus and return the exit code to use.
+async fn run_session(args: &RunSessionArgs) -> Result<i32> {
+    let (config_path, mut config) = read_session_config(args.config_file.clone())?;
+    // The bus belongs to the program, so it's neither shared nor detached.
+    config.listen = Some(bus::parse_address(&format!(
+        "unix:dir={}",
+        temp_dir().display()
+    ))?);
+    config.fork = false;
+    config.pidfile = None;
+
+    let mut bus = bus::Bus::for_config(config).await?;
+    if let Some(path) = &config_path {
+        bus.set_config_file(path);
+    }
+    let address = bus.address().map(ToString::to_string).unwrap_or_default();
+
+    let mut sig_int = tokio::signal::unix::signal(SignalKind::interrupt())?;
+    let mut sig_term = tokio::signal::unix::signal(SignalKind::terminate())?;
+    let mut sig_hup = tokio::signal::unix::signal(SignalKind::hangup())?;
+
+    let (program, program_args) = args
+        .command
+        .split_first()
+        .expect("clap should require a program");
+    let spawned = tokio::process::Command::new(program)
+        .args(program_args)
+        .env("DBUS_SESSION_BUS_ADDRESS", &address)
+        // These would be about another bus.
+        .env_remove("DBUS_SESSION_BUS_PID")
+        .env_remove("DBUS_SESSION_BUS_WINDOWID")
+        .env_remove("DBUS_STARTER_ADDRESS")
+        .env_remove("DBUS_STARTER_BUS_TYPE")
+        .spawn();
+    let mut child = match spawned {
+        Ok(child) => child,
+        Err(e) => {
+            error!("Failed to run `{}`: {e}", program.to_string_lossy());
+            bus.cleanup().await?;
+
+            // What shells use for commands that can't be run.
+            return Ok(127);
+        }
+    };
+
+    let mut bus_running = true;
+    let status = loop {
+        let signal = select! {
+            status = child.wait() => break status?,
+            _ = sig_int.recv() => Signal::SIGINT,
+            _ = sig_term.recv() => Signal::SIGTERM,
+            _ = sig_hup.recv() => Signal::SIGHUP,
+            res = bus.run(), if bus_running => {
+                match res {
+                    Ok(()) => error!("Bus stopped, waiting for the program to exit.."),
+                    Err(e) => error!("Bus stopped with an error: {}", e),
+                }
+                bus_running = false;
+
+                continue;
+            }
+        };
+        if let Some(pid) = child.id() {
+            if let Err(e) = kill(Pid::from_raw(pid as i32), signal) {
+                warn!("Failed to pass {signal} on to the program: {e}");
+            }
+        }
+    };
+
+    if let Err(e) = bus.shutdown(SHUTDOWN_TIMEOUT).await {
         error!("Failed to clean up: {}", e);
     }
 
+    // Like shells do for programs killed by a signal.
+    Ok(status
+        .code()
+        .or_else(|| status.signal().map(|signal| 128 + signal))
+        .unwrap_or(1))
+}
+
+/// Start a session bus in the background and print how to reach it.
+fn launch(args: &Args, launch_args: &LaunchArgs) -> Result<()> {
+    let (config_path, config) = read_session_config(launch_args.config_file.clone())?;
+    let syslog = if config.syslog {
+        Syslog::On
+    } else {
+        Syslog::Off
+    };
+    busd::tracing_subscriber::init_with_syslog(syslog);
+    // Whoever ran us, typically the shell of the session.
+    let session = launch_args.exit_with_session.then(getppid);
+
+    // This has to happen before the runtime spawns any threads.
+    let readiness = match daemonize(config.keep_umask, &[])? {
+        Daemonized::Parent { pid, address } => {
+            return print_session_vars(launch_args, &address, pid)
+        }
+        Daemonized::Daemon(readiness) => readiness,
+    };
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?
+        .block_on(run(
+            args,
+            config_path.as_deref(),
+            config,
+            Some(readiness),
+            session,
+        ))
+}
+
+/// Print the environment variables for the session bus at `address`, in the syntax asked for.
+fn print_session_vars(args: &LaunchArgs, address: &str, pid: Pid) -> Result<()> {
+    let mut stdout = std::io::stdout();
+    if args.binary_syntax {
+        // No X window to speak of.
+        let window_id: std::ffi::c_long = 0;
+        stdout.write_all(address.as_bytes())?;
+        stdout.write_all(b"\0")?;
+        stdout.write_all(&pid.as_raw().to_ne_bytes())?;
+        stdout.write_all(&window_id.to_ne_bytes())?;
+    } else if args.sh_syntax {
+        let address = shell_quote(address);
+        write!(
+            stdout,
+            "DBUS_SESSION_BUS_ADDRESS={address};\n\
+             export DBUS_SESSION_BUS_ADDRESS;\n\
+             DBUS_SESSION_BUS_PID={pid};\n"
+        )?;
+    } else if args.csh_syntax {
+        let address = shell_quote(address);
+        write!(
+            stdout,
+            "setenv DBUS_SESSION_BUS_ADDRESS {address};\n\
+             set DBUS_SESSION_BUS_PID={pid};\n"
+        )?;
+    } else {
+        write!(
+            stdout,
+            "DBUS_SESSION_BUS_ADDRESS={address}\nDBUS_SESSION_BUS_PID={pid}\n"
+        )?;
+    }
+
+    Ok(stdout.flush()?)
+}
+
+/// Quote `s` for both Bourne and C shells.
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+/// Wait for the process `pid` to exit.
+///
+/// As it's not our child, all we can do is checking every now and then.
+async fn process_exit(pid: Pid) {
+    loop {
+        sleep(PROCESS_CHECK_INTERVAL).await;
+        if kill(pid, None) == Err(Errno::ESRCH) {
+            return;
+        }
+    }
+}
+
+/// Read the configuration for a session bus from `config_file`, or the standard session
+/// configuration if there's none.
+///
+/// If the standard configuration isn't installed either, the defaults are used and no path is
+/// returned.
+fn read_session_config(config_file: Option<PathBuf>) -> Result<(Option<PathBuf>, Config)> {
+    let config_path = match config_file {
+        Some(path) => Some(path),
+        None => Some(PathBuf::from(SESSION_CONFIG)).filter(|path| path.exists()),
+    };
+    let config = match &config_path {
+        Some(path) => Config::read_file(path)?,
+        None => Config::default(),
+    };
+
+    Ok((config_path, config))
+}
+
+/// Tell the service manager we're ready, and keep it posted.
+async fn start_keep_alive(bus: &bus::Bus, notifier: Arc<Notifier>) -> Result<JoinHandle<()>> {
+    notifier.ready()?;
+    let conn = bus.connect_in_process().await?;
+    let watchdog = sd_notify::watchdog_interval();
+
+    Ok(tokio::spawn(async move {
+        if let Err(e) = notifier.keep_alive(&conn, watchdog).await {
+            error!("Stopped notifying the service manager: {e:#}");
+        }
+    }))
+}
+
+/// Let the world know the bus is listening on `address`.
+fn announce(args: &Args, address: &str, pid: u32) -> Result<()> {
+    // Several can go to the same file descriptor, in this order.
+    let mut output = BTreeMap::<i32, String>::new();
+    if let Some(fd) = args.ready_fd {
+        output.entry(fd).or_default().push_str("READY=1\n");
+    }
+    if let Some(fd) = args.print_address {
+        output
+            .entry(fd)
+            .or_default()
+            .push_str(&format!("{address}\n"));
+    }
+    if let Some(fd) = args.print_pid {
+        output.entry(fd).or_default().push_str(&format!("{pid}\n"));
+    }
+    for (fd, text) in output {
+        if fd == 1 {
+            print!("{text}");
+            std::io::stdout().flush()?;
+
+            continue;
+        }
+
+        // SAFETY: We don't have any way to know if the fd is valid or not. The parent process is
+        // responsible for passing a valid fd.
+        let mut file = ManuallyDrop::new(unsafe { File::from_raw_fd(fd) });
+        let res = file
+            .write_all(text.as_bytes())
+            .with_context(|| format!("Failed to write to file descriptor {fd}"));
+        // We're done with it then, unless it's one of the standard streams.
+        if fd > 2 {
+            drop(ManuallyDrop::into_inner(file));
+        }
+        res?;
+    }
+
+    Ok(())
+}
+
+/// Print the introspection data of `/org/freedesktop/DBus`, as served by a bus.
+async fn introspect() -> Result<()> {
+    let bus = bus::Bus::without_listener().await?;
+    let conn = bus.connect_in_process().await?;
+    let xml = IntrospectableProxy::builder(&conn)
+        .destination(fdo::BUS_NAME)?
+        .path(fdo::DBus::PATH)?
+        .build()
+        .await?
+        .introspect()
+        .await?;
+    print!("{xml}");
+
     Ok(())
 }
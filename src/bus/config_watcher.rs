@@ -0,0 +1,221 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::{OsStr, OsString},
+    io,
+    os::fd::{AsFd, AsRawFd, RawFd},
+    path::{Path, PathBuf},
+    sync::{Arc, Weak},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify, InotifyEvent, WatchDescriptor};
+use tokio::{io::unix::AsyncFd, spawn, task::JoinHandle, time::timeout};
+use tracing::{debug, info, warn};
+
+use super::LiveConfig;
+use crate::config::{Config, Sources};
+
+/// How long things need to be quiet after a change before reloading, so that a batch of changes
+/// (e.g. a package installing several files) only causes a single reload.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Reloads the configuration whenever one of the files it was read from changes.
+///
+/// Watching stops when this is dropped.
+#[derive(Debug)]
+pub(super) struct ConfigWatcher {
+    task: JoinHandle<()>,
+}
+
+impl ConfigWatcher {
+    pub(super) fn start(config: &Arc<LiveConfig>) -> Result<Self> {
+        let path = config
+            .file()
+            .ok_or_else(|| anyhow!("No configuration file to watch."))?;
+        let (_, sources) = Config::read_file_with_sources(path)?;
+        let watches = Watches::new(&sources)?;
+        let task = spawn(watch(Arc::downgrade(config), watches));
+
+        Ok(Self { task })
+    }
+}
+
+impl Drop for ConfigWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn watch(config: Weak<LiveConfig>, mut watches: Watches) {
+    let mut missed = false;
+    loop {
+        if !missed {
+            if let Err(e) = watches.changed().await {
+                warn!("Stopped watching the configuration: {e}");
+
+                return;
+            }
+        }
+        loop {
+            match timeout(DEBOUNCE, watches.changed()).await {
+                Ok(Ok(())) => continue,
+                Ok(Err(e)) => {
+                    warn!("Stopped watching the configuration: {e}");
+
+                    return;
+                }
+                Err(_) => break,
+            }
+        }
+
+        let Some(config) = config.upgrade() else {
+            return;
+        };
+        info!("Configuration changed, reloading..");
+        let sources = match config.reload().await {
+            Ok(sources) => sources,
+            Err(e) => {
+                // The files being watched are still the same, as the old configuration is kept.
+                warn!("{e:#}");
+                missed = false;
+
+                continue;
+            }
+        };
+        match Watches::new(&sources) {
+            Ok(new_watches) => {
+                // Changes made while reloading were only seen by the old watches.
+                missed = watches.pending();
+                watches = new_watches;
+            }
+            Err(e) => {
+                warn!("Failed to watch the new configuration files: {e:#}");
+                missed = false;
+            }
+        }
+    }
+}
+
+/// Inotify watches on the directories containing the configuration files.
+///
+/// Watching the directories rather than the files themselves means we also notice files being
+/// replaced, as editors and package managers do, and new files in `<includedir>` directories.
+#[derive(Debug)]
+struct Watches {
+    inotify: AsyncFd<InotifyFd>,
+    dirs: HashMap<WatchDescriptor, WatchedDir>,
+}
+
+/// The files we care about in a watched directory.
+#[derive(Debug, Default)]
+struct WatchedDir {
+    names: HashSet<OsString>,
+    conf_files: bool,
+}
+
+impl Watches {
+    fn new(sources: &Sources) -> Result<Self> {
+        let mut dirs = HashMap::<PathBuf, WatchedDir>::new();
+        for file in &sources.files {
+            let (Some(dir), Some(name)) = (file.parent(), file.file_name()) else {
+                continue;
+            };
+            let dir = if dir.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                dir
+            };
+            dirs.entry(dir.to_path_buf())
+                .or_default()
+                .names
+                .insert(name.to_owned());
+        }
+        for dir in &sources.dirs {
+            dirs.entry(dir.clone()).or_default().conf_files = true;
+        }
+
+        let inotify = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC)?;
+        let mask = AddWatchFlags::IN_CLOSE_WRITE
+            | AddWatchFlags::IN_CREATE
+            | AddWatchFlags::IN_DELETE
+            | AddWatchFlags::IN_MOVED_FROM
+            | AddWatchFlags::IN_MOVED_TO
+            | AddWatchFlags::IN_DELETE_SELF
+            | AddWatchFlags::IN_MOVE_SELF;
+        let mut watched = HashMap::<WatchDescriptor, WatchedDir>::new();
+        for (path, dir) in dirs {
+            match inotify.add_watch(&path, mask) {
+                // Different paths can lead to the same directory, giving the same watch.
+                Ok(wd) => {
+                    let watched = watched.entry(wd).or_default();
+                    watched.names.extend(dir.names);
+                    watched.conf_files |= dir.conf_files;
+                    debug!("Watching `{}` for configuration changes.", path.display());
+                }
+                Err(e) => warn!("Failed to watch `{}`: {e}", path.display()),
+            }
+        }
+
+        Ok(Self {
+            inotify: AsyncFd::new(InotifyFd(inotify))?,
+            dirs: watched,
+        })
+    }
+
+    /// Wait for a change to the configuration files.
+    async fn changed(&self) -> io::Result<()> {
+        loop {
+            let mut guard = self.inotify.readable().await?;
+            match guard.try_io(|inotify| inotify.get_ref().0.read_events().map_err(io::Error::from))
+            {
+                Ok(Ok(events)) if self.any_relevant(&events) => return Ok(()),
+                Ok(Ok(_)) => (),
+                Ok(Err(e)) => return Err(e),
+                // Spurious wake up.
+                Err(_) => (),
+            }
+        }
+    }
+
+    /// Whether the configuration files changed since the last call to [`Watches::changed`].
+    fn pending(&self) -> bool {
+        match self.inotify.get_ref().0.read_events() {
+            Ok(events) => self.any_relevant(&events),
+            Err(_) => false,
+        }
+    }
+
+    fn any_relevant(&self, events: &[InotifyEvent]) -> bool {
+        events.iter().any(|event| {
+            // We can't know what we missed.
+            if event.mask.contains(AddWatchFlags::IN_Q_OVERFLOW) {
+                return true;
+            }
+
+            match (self.dirs.get(&event.wd), &event.name) {
+                (Some(dir), Some(name)) => dir.contains(name),
+                // Something happened to the directory itself.
+                (Some(_), None) => true,
+                (None, _) => false,
+            }
+        })
+    }
+}
+
+/// [`AsyncFd`] needs [`AsRawFd`], which [`Inotify`] doesn't implement.
+#[derive(Debug)]
+struct InotifyFd(Inotify);
+
+impl AsRawFd for InotifyFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_fd().as_raw_fd()
+    }
+}
+
+impl WatchedDir {
+    fn contains(&self, name: &OsStr) -> bool {
+        self.names.contains(name)
+            || (self.conf_files && Path::new(name).extension() == Some(OsStr::new("conf")))
+    }
+}
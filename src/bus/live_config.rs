@@ -0,0 +1,140 @@
+use std::{
+    fmt::Debug,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+use anyhow::{anyhow, Context, Result};
+use nix::unistd::Uid;
+use tracing::{info, warn};
+
+use crate::{
+    auth::ConnectPolicy,
+    config::{Config, Limits, Sources},
+};
+
+/// The configuration of a running bus.
+///
+/// Policies, service directories and limits can be reloaded from the configuration file while the
+/// bus is running. They're swapped all at once, so no one ever sees a mix of the old and new ones.
+/// Everything else only takes effect on startup.
+#[derive(Debug)]
+pub struct LiveConfig {
+    owner: Uid,
+    file: RwLock<Option<PathBuf>>,
+    current: RwLock<Arc<Current>>,
+}
+
+#[derive(Debug)]
+struct Current {
+    config: Arc<Config>,
+    connect_policy: Arc<ConnectPolicy>,
+}
+
+impl LiveConfig {
+    pub(crate) fn new(owner: Uid, config: Config) -> Self {
+        Self {
+            owner,
+            file: RwLock::new(None),
+            current: RwLock::new(Arc::new(Current::new(owner, config))),
+        }
+    }
+
+    pub(crate) fn config(&self) -> Arc<Config> {
+        self.current().config.clone()
+    }
+
+    pub(crate) fn connect_policy(&self) -> Arc<ConnectPolicy> {
+        self.current().connect_policy.clone()
+    }
+
+    pub(crate) fn limits(&self) -> Limits {
+        self.current().config.limits.clone()
+    }
+
+    pub(crate) fn file(&self) -> Option<PathBuf> {
+        self.file.read().expect("poisoned lock").clone()
+    }
+
+    pub(crate) fn set_file(&self, path: &Path) {
+        *self.file.write().expect("poisoned lock") = Some(path.to_path_buf());
+    }
+
+    /// Re-read the configuration file and apply what can be changed at runtime.
+    ///
+    /// If the file can't be read or parsed, the current configuration is kept. Otherwise, the
+    /// files and directories the new configuration was read from are returned.
+    pub(crate) async fn reload(&self) -> Result<Sources> {
+        let path = self
+            .file()
+            .ok_or_else(|| anyhow!("No server configuration to reload."))?;
+        let read_path = path.clone();
+        let (new, sources) =
+            tokio::task::spawn_blocking(move || Config::read_file_with_sources(read_path))
+                .await?
+                .with_context(|| {
+                    format!("Failed to reload configuration from `{}`", path.display())
+                })?;
+        self.apply(new);
+        info!("Reloaded configuration from `{}`.", path.display());
+
+        Ok(sources)
+    }
+
+    /// Swap in the runtime settings of `new`.
+    fn apply(&self, new: Config) {
+        let mut current = self.current.write().expect("poisoned lock");
+        let old = &current.config;
+
+        let startup_only = |c: &Config| {
+            (
+                c.allow_anonymous,
+                c.auth.clone(),
+                c.fork,
+                c.keep_umask,
+                c.syslog,
+                c.r#type.clone(),
+                c.user.clone(),
+            )
+        };
+        if startup_only(old) != startup_only(&new) {
+            warn!("Only policies, service directories and limits can change without a restart.");
+        }
+
+        let config = Config {
+            limits: new.limits,
+            policies: new.policies,
+            servicedirs: new.servicedirs,
+            servicehelper: new.servicehelper,
+            ..(**old).clone()
+        };
+        log_changes("service directory", &old.servicedirs, &config.servicedirs);
+        log_changes("policy", &old.policies, &config.policies);
+        *current = Arc::new(Current::new(self.owner, config));
+    }
+
+    fn current(&self) -> Arc<Current> {
+        self.current.read().expect("poisoned lock").clone()
+    }
+}
+
+impl Current {
+    fn new(owner: Uid, config: Config) -> Self {
+        Self {
+            connect_policy: Arc::new(ConnectPolicy::new(owner, &config.policies)),
+            config: Arc::new(config),
+        }
+    }
+}
+
+fn log_changes<T>(what: &str, old: &[T], new: &[T])
+where
+    T: Debug + PartialEq,
+{
+    for added in new.iter().filter(|item| !old.contains(item)) {
+        info!("Added {what}: {added:?}");
+    }
+    for removed in old.iter().filter(|item| !new.contains(item)) {
+        info!("Removed {what}: {removed:?}");
+    }
+}
@@ -1,49 +1,129 @@
-use anyhow::{bail, Ok, Result};
-use std::{env, path::Path, str::FromStr, sync::Arc};
-use tokio::{fs::remove_file, spawn};
+use anyhow::{anyhow, bail, Context, Ok, Result};
+use nix::{
+    fcntl::{Flock, FlockArg},
+    sys::socket::{self, AddressFamily, Backlog, SockFlag, SockType, UnixAddr},
+    unistd::{Gid, Uid, User},
+};
+use std::{
+    env,
+    fs::{set_permissions, File, OpenOptions, Permissions},
+    io,
+    os::{
+        fd::{AsRawFd, OwnedFd},
+        unix::fs::{chown, FileTypeExt, PermissionsExt},
+    },
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{
+    fs::remove_file,
+    net::lookup_host,
+    spawn,
+    time::{timeout, Instant},
+};
 use tracing::{debug, info, trace, warn};
 use zbus::{
     address::{
-        transport::{Tcp, Unix, UnixSocket},
+        transport::{Tcp, TcpTransportFamily, Unix, UnixSocket},
         Transport,
     },
-    connection::{self, socket::BoxedSplit},
-    Address, AuthMechanism, Connection, Guid, OwnedGuid,
+    connection::{
+        self,
+        socket::{BoxedSplit, Channel},
+    },
+    Address, Connection, Guid, OwnedGuid,
 };
 
+#[cfg(target_os = "linux")]
+mod config_watcher;
+#[cfg(target_os = "linux")]
+use config_watcher::ConfigWatcher;
+mod live_config;
+pub use live_config::LiveConfig;
+mod pidfile;
+use pidfile::Pidfile;
+mod privileges;
+
 use crate::{
+    auth::{self, Mechanism},
+    config::{BusType, Config, IpNetwork},
     fdo::{self, DBus, Monitoring},
-    peers::Peers,
+    peers::{ConnectionCounts, Peers},
 };
 
 /// The bus.
 #[derive(Debug)]
 pub struct Bus {
     inner: Inner,
-    listener: Listener,
+    listener: Option<Listener>,
+    socket_lock: Option<SocketLock>,
+    pidfile: Option<Pidfile>,
+    #[cfg(target_os = "linux")]
+    config_watcher: Option<ConfigWatcher>,
 }
 
 // All (cheaply) cloneable fields of `Bus` go here.
 #[derive(Clone, Debug)]
 pub struct Inner {
-    address: Address,
+    address: Option<Address>,
     peers: Arc<Peers>,
     guid: OwnedGuid,
-    next_id: usize,
-    auth_mechanism: AuthMechanism,
+    next_id: Arc<AtomicUsize>,
+    auth_mechanisms: Vec<Mechanism>,
+    config: Arc<LiveConfig>,
+    incomplete_connections: Arc<AtomicUsize>,
+    dropped_connections: Arc<AtomicUsize>,
     _self_conn: Connection,
 }
 
 #[derive(Debug)]
 enum Listener {
     Unix(tokio::net::UnixListener),
-    Tcp(tokio::net::TcpListener),
+    /// A TCP listener, only accepting clients from the given networks if any.
+    Tcp(tokio::net::TcpListener, Vec<IpNetwork>),
+}
+
+/// An advisory lock on a file next to a UNIX socket file.
+///
+/// As long as this is held, no other busd instance will try to listen on the same socket file.
+#[derive(Debug)]
+struct SocketLock {
+    path: PathBuf,
+    /// Until the bus is set up, failing to do so means the lock file is ours to remove. After
+    /// that, it's up to [`Bus::cleanup`].
+    remove_on_drop: bool,
+    _lock: Flock<File>,
+}
+
+/// Ownership and mode to give to UNIX socket files we listen on.
+#[derive(Clone, Debug, Default)]
+struct SocketPermissions {
+    mode: Option<u32>,
+    owner: Option<(Uid, Gid)>,
 }
 
 impl Bus {
     pub async fn for_address(address: Option<&str>) -> Result<Self> {
-        let mut address = match address {
-            Some(address) => Address::from_str(address)?,
+        let listen = address.map(parse_address).transpose()?;
+
+        Self::for_config(Config {
+            listen,
+            ..Default::default()
+        })
+        .await
+    }
+
+    /// Create a bus from the given configuration.
+    ///
+    /// If `config` doesn't specify an address to listen on, the default address is used.
+    pub async fn for_config(config: Config) -> Result<Self> {
+        let mut address = match config.listen.clone() {
+            Some(address) => address,
             None => Address::from_str(&default_address())?,
         };
         let guid: OwnedGuid = match address.guid() {
@@ -55,7 +135,13 @@ impl Bus {
                 guid.into()
             }
         };
-        let (listener, auth_mechanism) = match address.transport() {
+        let auth_mechanisms = auth_mechanisms(&config, address.transport())?;
+        let user = config.user.as_deref().map(resolve_user).transpose()?;
+        let owner = match &user {
+            Some(user) => user.uid,
+            None => Uid::effective(),
+        };
+        let (listener, socket_lock) = match address.transport() {
             Transport::Unix(unix) => {
                 // Resolve address specification into address that clients can use.
                 let addr = Self::unix_addr(unix)?;
@@ -66,23 +152,83 @@ impl Bus {
                 ))))
                 .set_guid(guid.clone())?;
 
-                (
-                    Self::unix_stream(addr.clone()).await?,
-                    AuthMechanism::External,
-                )
+                let permissions = SocketPermissions::for_config(&config)?;
+
+                Self::unix_stream(addr.clone(), permissions).await?
+            }
+            Transport::Tcp(tcp) => {
+                let listener = Self::tcp_stream(tcp, config.tcp_allowed_clients.clone()).await?;
+                // Resolve address specification into address that clients can use.
+                let port = match &listener {
+                    Listener::Tcp(listener, _) => listener.local_addr()?.port(),
+                    _ => unreachable!("TCP address should always give a TCP listener."),
+                };
+                address = Address::new(Transport::Tcp(
+                    Tcp::new(tcp.host(), port).set_family(tcp.family()),
+                ))
+                .set_guid(guid.clone())?;
+
+                (listener, None)
             }
-            Transport::Tcp(tcp) => (Self::tcp_stream(tcp).await?, AuthMechanism::Anonymous),
             _ => bail!("Unsupported address `{}`.", address),
         };
 
+        // Only claim to be running once we're listening.
+        let pidfile = config.pidfile.as_deref().map(Pidfile::create).transpose()?;
+
+        // Whatever needed privileges is done, so give them up before any client gets in.
+        if let Some(user) = &user {
+            privileges::drop_privileges(user)?;
+        }
+
+        let mut bus = Self::new(
+            Some(address),
+            Some(listener),
+            guid,
+            auth_mechanisms,
+            LiveConfig::new(owner, config),
+        )
+        .await?;
+        bus.socket_lock = socket_lock.map(SocketLock::keep);
+        bus.pidfile = pidfile;
+
+        Ok(bus)
+    }
+
+    /// Create a bus that doesn't listen for connections.
+    ///
+    /// Peers can only be added through [`Bus::add_connection`], [`Bus::add_connection_fd`] and
+    /// [`Bus::connect_in_process`]. As there's no configuration, only processes running as the
+    /// same user as the bus are allowed.
+    pub async fn without_listener() -> Result<Self> {
+        let auth_mechanisms = vec![Mechanism::External];
+
+        Self::new(
+            None,
+            None,
+            Guid::generate().into(),
+            auth_mechanisms,
+            LiveConfig::new(Uid::effective(), Config::default()),
+        )
+        .await
+    }
+
+    async fn new(
+        address: Option<Address>,
+        listener: Option<Listener>,
+        guid: OwnedGuid,
+        auth_mechanisms: Vec<Mechanism>,
+        config: LiveConfig,
+    ) -> Result<Self> {
         let peers = Peers::new();
+        let config = Arc::new(config);
 
-        let dbus = DBus::new(peers.clone(), guid.clone());
+        let dbus = DBus::new(peers.clone(), guid.clone(), config.clone());
         let monitoring = Monitoring::new(peers.clone());
 
         // Create a peer for ourselves.
         trace!("Creating self-dial connection.");
-        let (client_socket, peer_socket) = zbus::connection::socket::Channel::pair();
+        let (client_socket, peer_socket) = Channel::pair();
         let service_conn = connection::Builder::authenticated_socket(client_socket, guid.clone())?
             .p2p()
             .unique_name(fdo::BUS_NAME)?
@@ -101,36 +247,171 @@ impl Bus {
 
         Ok(Self {
             listener,
+            socket_lock: None,
+            pidfile: None,
+            #[cfg(target_os = "linux")]
+            config_watcher: None,
             inner: Inner {
                 address,
                 peers,
                 guid,
-                next_id: 0,
-                auth_mechanism,
+                next_id: Arc::new(AtomicUsize::new(0)),
+                auth_mechanisms,
+                config,
+                incomplete_connections: Arc::new(AtomicUsize::new(0)),
+                dropped_connections: Arc::new(AtomicUsize::new(0)),
                 _self_conn: service_conn,
             },
         })
     }
 
-    pub fn address(&self) -> &Address {
-        &self.inner.address
+    /// The address the bus is listening on, if any.
+    pub fn address(&self) -> Option<&Address> {
+        self.inner.address.as_ref()
+    }
+
+    /// The configuration currently in effect.
+    pub fn config(&self) -> Arc<Config> {
+        self.inner.config.config()
+    }
+
+    /// The configuration file to re-read on [`Bus::reload_config`].
+    pub fn set_config_file(&self, path: &Path) {
+        self.inner.config.set_file(path);
+    }
+
+    /// Re-read the configuration file and apply the policies, service directories and limits in
+    /// it, without dropping any connections.
+    ///
+    /// Other settings only take effect on startup. If the file fails to parse, the current
+    /// configuration is kept and an error is returned.
+    pub async fn reload_config(&self) -> Result<()> {
+        self.inner.config.reload().await.map(|_| ())
     }
 
+    /// Reload the configuration whenever the configuration file, a file it includes or a file in
+    /// one of its `<includedir>` directories changes.
+    ///
+    /// Watching stops when the bus is dropped.
+    #[cfg(target_os = "linux")]
+    pub fn watch_config_file(&mut self) -> Result<()> {
+        self.config_watcher = Some(ConfigWatcher::start(&self.inner.config)?);
+
+        Ok(())
+    }
+
+    /// Connect to the bus from within the same process.
+    ///
+    /// The returned connection is already authenticated and has been assigned a unique name. No
+    /// socket is involved so this works regardless of the address the bus is listening on.
+    ///
+    /// As they come from the bus process itself, such connections are not subject to
+    /// `max_completed_connections` and `max_connections_per_user`. They still count towards the
+    /// former, since they use up resources like any other.
+    pub async fn connect_in_process(&self) -> Result<Connection> {
+        let (client_socket, peer_socket) = Channel::pair();
+        let id = self.next_id();
+        self.inner
+            .peers
+            .add_in_process(&self.inner.guid, id, peer_socket)
+            .await?;
+
+        let conn =
+            connection::Builder::authenticated_socket(client_socket, self.inner.guid.clone())?
+                .build()
+                .await?;
+        // Without a handshake, it's up to us to say hello.
+        let unique_name = zbus::fdo::DBusProxy::new(&conn).await?.hello().await?;
+        conn.set_unique_name(unique_name)?;
+
+        Ok(conn)
+    }
+
+    /// Add a peer connected through `stream`.
+    ///
+    /// The usual authentication handshake takes place on `stream`, using the same mechanism as for
+    /// connections coming through the listener. If `uid` is given, the peer is treated as that
+    /// user, regardless of the credentials of the process on the other end.
+    ///
+    /// An error is returned if the connection is refused because `max_incomplete_connections`
+    /// connections are already waiting to authenticate. Failures of the handshake itself happen
+    /// later on and are only logged.
+    pub fn add_connection(&self, stream: tokio::net::UnixStream, uid: Option<u32>) -> Result<()> {
+        let socket = stream.into();
+        let socket = match uid {
+            Some(uid) => auth::override_uid(socket, uid),
+            None => socket,
+        };
+        self.add_peer(socket, uid)
+    }
+
+    /// Same as [`Bus::add_connection`] but for a connected UNIX socket given as a file descriptor.
+    pub fn add_connection_fd(&self, fd: OwnedFd, uid: Option<u32>) -> Result<()> {
+        let stream = std::os::unix::net::UnixStream::from(fd);
+        stream.set_nonblocking(true)?;
+        let stream = tokio::net::UnixStream::from_std(stream)?;
+
+        self.add_connection(stream, uid)
+    }
+
+    /// Accept and serve connections.
+    ///
+    /// If the bus has no listener, this never returns.
     pub async fn run(&mut self) -> Result<()> {
+        if self.listener.is_none() {
+            return std::future::pending().await;
+        }
+
         loop {
             self.accept_next().await?;
         }
     }
 
+    /// Shut down the bus gracefully.
+    ///
+    /// No new connections are accepted from now on. Peers are then disconnected once the messages
+    /// being sent to them went out, or after `flush_timeout` at the latest. Finally, the bus is
+    /// cleaned up as with [`Bus::cleanup`].
+    pub async fn shutdown(mut self, flush_timeout: Duration) -> Result<()> {
+        // Clients trying to connect are refused from now on.
+        self.listener = None;
+        self.inner
+            .peers
+            .shutdown(Instant::now() + flush_timeout)
+            .await;
+
+        self.cleanup().await
+    }
+
     // AsyncDrop would have been nice!
     pub async fn cleanup(self) -> Result<()> {
-        match self.inner.address.transport() {
-            Transport::Unix(unix) => match unix.path() {
-                UnixSocket::File(path) => remove_file(path).await.map_err(Into::into),
-                _ => Ok(()),
-            },
-            _ => Ok(()),
+        let mut paths = Vec::new();
+        if let Some(Transport::Unix(unix)) = self.inner.address.as_ref().map(Address::transport) {
+            if let UnixSocket::File(path) = unix.path() {
+                paths.push(path.to_path_buf());
+            }
+        }
+        // Only remove the lock file after the socket file is gone, so that another instance can't
+        // sneak in between.
+        if let Some(lock) = &self.socket_lock {
+            paths.push(lock.path.clone());
+        }
+        if let Some(pidfile) = &self.pidfile {
+            paths.push(pidfile.path().to_path_buf());
+        }
+
+        // Having lost the privileges to remove one of them doesn't mean we can't remove the others.
+        let mut res = Ok(());
+        for path in paths {
+            if let Err(e) = remove_file(&path).await {
+                warn!("Failed to remove `{}`: {e}", path.display());
+                if res.is_ok() {
+                    res = Err(e).with_context(|| format!("Failed to remove `{}`", path.display()));
+                }
+            }
         }
+
+        res
     }
 
     fn unix_addr(unix: &Unix) -> Result<std::os::unix::net::SocketAddr> {
@@ -172,47 +453,129 @@ impl Bus {
         })
     }
 
-    async fn unix_stream(addr: std::os::unix::net::SocketAddr) -> Result<Listener> {
+    async fn unix_stream(
+        addr: std::os::unix::net::SocketAddr,
+        permissions: SocketPermissions,
+    ) -> Result<(Listener, Option<SocketLock>)> {
         // TODO: Use tokio::net::UnixListener directly once it supports abstract sockets:
         //
         // https://github.com/tokio-rs/tokio/issues/4610
 
-        let std_listener =
-            tokio::task::spawn_blocking(move || std::os::unix::net::UnixListener::bind_addr(&addr))
-                .await??;
+        let (std_listener, lock) = tokio::task::spawn_blocking(move || {
+            let lock = match addr.as_pathname() {
+                Some(path) => {
+                    let lock = SocketLock::acquire(path)?;
+                    remove_stale_socket(path)?;
+
+                    Some(lock)
+                }
+                // Abstract sockets vanish with their owner so they can't go stale.
+                None => None,
+            };
+            let listener = match addr.as_pathname() {
+                Some(path) => permissions.bind(path)?,
+                None => std::os::unix::net::UnixListener::bind_addr(&addr)?,
+            };
+
+            Ok((listener, lock))
+        })
+        .await??;
         std_listener.set_nonblocking(true)?;
-        tokio::net::UnixListener::from_std(std_listener)
-            .map(Listener::Unix)
-            .map_err(Into::into)
+        let listener = tokio::net::UnixListener::from_std(std_listener)?;
+
+        Ok((Listener::Unix(listener), lock))
     }
 
-    async fn tcp_stream(tcp: &Tcp) -> Result<Listener> {
+    async fn tcp_stream(tcp: &Tcp, allowed_clients: Vec<IpNetwork>) -> Result<Listener> {
         if tcp.nonce_file().is_some() {
             bail!("`nonce-tcp` transport is not supported (yet).");
         }
-        info!("Listening on `{}:{}`.", tcp.host(), tcp.port());
-        let address = (tcp.host(), tcp.port());
 
-        tokio::net::TcpListener::bind(address)
-            .await
-            .map(Listener::Tcp)
-            .map_err(Into::into)
+        // We listen on `bind` but advertise `host`.
+        let host = match (tcp.bind().unwrap_or(tcp.host()), tcp.family()) {
+            ("*", Some(TcpTransportFamily::Ipv6)) => "::",
+            ("*", _) => "0.0.0.0",
+            (host, _) => host,
+        };
+        let mut last_err = None;
+        for addr in lookup_host((host, tcp.port())).await? {
+            match tcp.family() {
+                Some(TcpTransportFamily::Ipv4) if !addr.is_ipv4() => continue,
+                Some(TcpTransportFamily::Ipv6) if !addr.is_ipv6() => continue,
+                _ => (),
+            }
+
+            match tokio::net::TcpListener::bind(addr).await {
+                Result::Ok(listener) => {
+                    info!("Listening on `{}`.", listener.local_addr()?);
+
+                    return Ok(Listener::Tcp(listener, allowed_clients));
+                }
+                Err(e) => {
+                    debug!("Failed to listen on `{addr}`: {e}");
+                    last_err = Some(e);
+                }
+            }
+        }
+
+        match last_err {
+            Some(e) => Err(e).with_context(|| format!("Failed to listen on `{host}`")),
+            None => bail!("`{host}` doesn't resolve to any address of the requested family."),
+        }
     }
 
     async fn accept_next(&mut self) -> Result<()> {
         let socket = self.accept().await?;
+        // A refused connection must not bring down the listener.
+        if let Err(e) = self.add_peer(socket, None) {
+            warn!("Dropping connection: {e}");
+        }
 
-        let id = self.next_id();
+        Ok(())
+    }
+
+    /// Authenticate the peer on `socket` and add it, as the user with the given `uid` if any.
+    fn add_peer(&self, mut socket: BoxedSplit, uid: Option<u32>) -> Result<()> {
         let inner = self.inner.clone();
+        // Settings in effect when the connection came in apply, even if reloaded in the meantime.
+        let limits = inner.config.limits();
+        let connect_policy = inner.config.connect_policy();
+        let max_incomplete = limits.max_incomplete_connections;
+        if inner.incomplete_connections.fetch_add(1, Ordering::SeqCst) >= max_incomplete {
+            inner.incomplete_connections.fetch_sub(1, Ordering::SeqCst);
+            inner.dropped_connections.fetch_add(1, Ordering::Relaxed);
+            bail!("Already {max_incomplete} connections waiting to authenticate");
+        }
+
+        let id = self.next_id();
         spawn(async move {
-            if let Err(e) = inner
-                .peers
-                .clone()
-                .add(&inner.guid, id, socket, inner.auth_mechanism)
-                .await
-            {
+            let add = async {
+                let authenticated =
+                    auth::handshake(&mut socket, &inner.guid, &inner.auth_mechanisms, uid, None)
+                        .await?;
+
+                inner
+                    .peers
+                    .add(
+                        &inner.guid,
+                        id,
+                        socket,
+                        authenticated,
+                        &connect_policy,
+                        &limits,
+                    )
+                    .await
+            };
+            let auth_timeout = limits.auth_timeout;
+            let res = timeout(auth_timeout, add).await.unwrap_or_else(|_| {
+                inner.dropped_connections.fetch_add(1, Ordering::Relaxed);
+
+                Err(anyhow!("Not authenticated after {auth_timeout:?}"))
+            });
+            if let Err(e) = res {
                 warn!("Failed to establish connection: {}", e);
             }
+            inner.incomplete_connections.fetch_sub(1, Ordering::SeqCst);
         });
 
         Ok(())
@@ -220,10 +583,26 @@ impl Bus {
 
     async fn accept(&mut self) -> Result<BoxedSplit> {
         let stream = match &mut self.listener {
-            Listener::Unix(listener) => listener.accept().await.map(|(stream, _)| stream.into())?,
-            Listener::Tcp(listener) => listener.accept().await.map(|(stream, _)| stream.into())?,
+            Some(Listener::Unix(listener)) => {
+                listener.accept().await.map(|(stream, _)| stream.into())?
+            }
+            Some(Listener::Tcp(listener, allowed_clients)) => loop {
+                let (stream, client) = listener.accept().await?;
+                let client_ip = client.ip();
+                if allowed_clients.is_empty()
+                    || allowed_clients.iter().any(|net| net.contains(&client_ip))
+                {
+                    break stream.into();
+                }
+
+                // Dropping the stream closes the connection.
+                warn!("Refusing connection from `{client}`, which is not an allowed client.");
+            },
+            None => bail!("The bus has no listener."),
         };
-        debug!("Accepted connection on address `{}`", self.inner.address);
+        if let Some(address) = &self.inner.address {
+            debug!("Accepted connection on address `{address}`");
+        }
 
         Ok(stream)
     }
@@ -236,14 +615,255 @@ impl Bus {
         &self.inner.guid
     }
 
-    pub fn auth_mechanism(&self) -> AuthMechanism {
-        self.inner.auth_mechanism
+    /// The authentication mechanisms offered to peers.
+    pub fn auth_mechanisms(&self) -> &[Mechanism] {
+        &self.inner.auth_mechanisms
+    }
+
+    /// The number of connections that are still authenticating.
+    pub fn incomplete_connections(&self) -> usize {
+        self.inner.incomplete_connections.load(Ordering::SeqCst)
+    }
+
+    /// The number of peers connected to the bus, in total and per user.
+    ///
+    /// These are what `max_completed_connections` and `max_connections_per_user` limit.
+    pub async fn connection_counts(&self) -> ConnectionCounts {
+        self.inner.peers.connection_counts().await
     }
 
-    fn next_id(&mut self) -> usize {
-        self.inner.next_id += 1;
+    /// The number of connections dropped for not authenticating in time or exceeding
+    /// `max_incomplete_connections`.
+    pub fn dropped_connections(&self) -> usize {
+        self.inner.dropped_connections.load(Ordering::Relaxed)
+    }
+
+    fn next_id(&self) -> usize {
+        self.inner.next_id.fetch_add(1, Ordering::Relaxed) + 1
+    }
+}
+
+impl SocketLock {
+    fn acquire(socket_path: &Path) -> Result<Self> {
+        let mut path = socket_path.as_os_str().to_owned();
+        path.push(".lock");
+        let path = PathBuf::from(path);
+
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .with_context(|| format!("Failed to open lock file `{}`", path.display()))?;
+        let lock =
+            Flock::lock(file, FlockArg::LockExclusiveNonblock).map_err(|(_, e)| match e {
+                nix::errno::Errno::EWOULDBLOCK => anyhow!(
+                    "Another bus instance holds the lock file `{}`.",
+                    path.display()
+                ),
+                e => anyhow::Error::new(e).context(format!("Failed to lock `{}`", path.display())),
+            })?;
+        debug!("Acquired lock file `{}`.", path.display());
+
+        Ok(Self {
+            path,
+            remove_on_drop: true,
+            _lock: lock,
+        })
+    }
+
+    /// Leave the lock file in place when dropped.
+    fn keep(mut self) -> Self {
+        self.remove_on_drop = false;
+
+        self
+    }
+}
+
+impl Drop for SocketLock {
+    fn drop(&mut self) {
+        if self.remove_on_drop {
+            if let Err(e) = std::fs::remove_file(&self.path) {
+                warn!("Failed to remove `{}`: {e}", self.path.display());
+            }
+        }
+    }
+}
 
-        self.inner.next_id
+impl SocketPermissions {
+    fn for_config(config: &Config) -> Result<Self> {
+        let mode = if config.r#type == Some(BusType::System) {
+            // Everyone needs to be able to connect to the system bus. Authentication and policies
+            // take care of the rest.
+            Some(0o666)
+        } else if config.keep_umask {
+            None
+        } else {
+            Some(0o600)
+        };
+        let owner = config
+            .user
+            .as_deref()
+            .map(resolve_user)
+            .transpose()?
+            .map(|user| (user.uid, user.gid));
+
+        Ok(Self { mode, owner })
+    }
+
+    /// Bind to `path`, which gets its permissions before any client can connect.
+    fn bind(&self, path: &Path) -> Result<std::os::unix::net::UnixListener> {
+        // Clients can't connect until we listen, so until then it doesn't matter what the umask
+        // made of the socket file's mode.
+        let fd = socket::socket(
+            AddressFamily::Unix,
+            SockType::Stream,
+            SockFlag::SOCK_CLOEXEC,
+            None,
+        )?;
+        socket::bind(fd.as_raw_fd(), &UnixAddr::new(path)?)
+            .with_context(|| format!("Failed to bind to `{}`", path.display()))?;
+
+        if let Some((uid, gid)) = self.owner {
+            if uid != Uid::effective() || gid != Gid::effective() {
+                chown(path, Some(uid.as_raw()), Some(gid.as_raw())).with_context(|| {
+                    format!("Failed to change owner of `{}` to {uid}", path.display())
+                })?;
+            }
+        }
+        if let Some(mode) = self.mode {
+            set_permissions(path, Permissions::from_mode(mode)).with_context(|| {
+                format!("Failed to set mode of `{}` to {mode:o}", path.display())
+            })?;
+        }
+        socket::listen(&fd, Backlog::MAXCONN)?;
+
+        Ok(fd.into())
+    }
+}
+
+/// Parse a D-Bus address that the bus can listen on.
+///
+/// zbus doesn't support the `bind` key of TCP addresses (yet), so we take care of it here.
+pub fn parse_address(address: &str) -> Result<Address> {
+    let options = match address.strip_prefix("tcp:") {
+        Some(options) => options,
+        None => return Address::from_str(address).map_err(Into::into),
+    };
+    let mut bind = None;
+    let options: Vec<_> = options
+        .split(',')
+        .filter(|option| match option.strip_prefix("bind=") {
+            Some(value) => {
+                bind = Some(value);
+
+                false
+            }
+            None => true,
+        })
+        .collect();
+    let parsed = Address::from_str(&format!("tcp:{}", options.join(",")))?;
+    let bind = match bind {
+        Some(bind) => decode_percents(bind)?,
+        None => return Ok(parsed),
+    };
+
+    let tcp = match parsed.transport() {
+        Transport::Tcp(tcp) => tcp.clone().set_bind(Some(bind)),
+        _ => unreachable!("`tcp:` address should always have TCP transport."),
+    };
+    let address = Address::new(Transport::Tcp(tcp));
+    match parsed.guid() {
+        Some(guid) => address.set_guid(guid.to_owned()).map_err(Into::into),
+        None => Ok(address),
+    }
+}
+
+fn decode_percents(value: &str) -> Result<String> {
+    let mut decoded = Vec::with_capacity(value.len());
+    let mut bytes = value.bytes();
+    while let Some(byte) = bytes.next() {
+        if byte != b'%' {
+            decoded.push(byte);
+
+            continue;
+        }
+
+        let hex = [
+            bytes.next().unwrap_or_default(),
+            bytes.next().unwrap_or_default(),
+        ];
+        let hex = std::str::from_utf8(&hex)?;
+        decoded.push(
+            u8::from_str_radix(hex, 16)
+                .with_context(|| format!("Invalid percent-encoding in `{value}`"))?,
+        );
+    }
+
+    String::from_utf8(decoded).map_err(Into::into)
+}
+
+/// The authentication mechanisms to offer to peers connecting through `transport`.
+fn auth_mechanisms(config: &Config, transport: &Transport) -> Result<Vec<Mechanism>> {
+    let mechanisms = if config.auth.is_empty() {
+        Mechanism::ALL
+            .iter()
+            .copied()
+            .filter(|m| m.supports(transport))
+            .collect()
+    } else {
+        if let Some(m) = config.auth.iter().find(|m| !m.supports(transport)) {
+            bail!("`{m}` authentication can't be used on `{transport}`.");
+        }
+
+        config.auth.clone()
+    };
+    let mechanisms: Vec<_> = mechanisms
+        .into_iter()
+        .filter(|m| *m != Mechanism::Anonymous || config.allow_anonymous)
+        .collect();
+    if mechanisms.is_empty() {
+        bail!("None of the allowed authentication mechanisms can be used on `{transport}`.");
+    }
+
+    Ok(mechanisms)
+}
+
+/// Resolve a user name, or a numeric UID, into the user account.
+fn resolve_user(user: &str) -> Result<User> {
+    let found = match user.parse::<u32>().ok() {
+        Some(uid) => User::from_uid(Uid::from_raw(uid))?,
+        None => User::from_name(user)?,
+    };
+
+    found.ok_or_else(|| anyhow!("No such user `{user}`."))
+}
+
+/// Remove the socket file at `path` if it's left over from a bus that is no longer running.
+///
+/// Fails if another bus is still listening on it, or if `path` is not a socket.
+fn remove_stale_socket(path: &Path) -> Result<()> {
+    let metadata = match path.symlink_metadata() {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        res => res?,
+    };
+    if !metadata.file_type().is_socket() {
+        bail!("`{}` exists and is not a socket.", path.display());
+    }
+
+    let e = match std::os::unix::net::UnixStream::connect(path) {
+        Err(e) => e,
+        _ => bail!("Another bus is already listening on `{}`.", path.display()),
+    };
+    match e.kind() {
+        io::ErrorKind::ConnectionRefused => {
+            warn!("Removing stale socket file `{}`.", path.display());
+            std::fs::remove_file(path)?;
+
+            Ok(())
+        }
+        io::ErrorKind::NotFound => Ok(()),
+        _ => Err(e).with_context(|| format!("Failed to probe `{}`", path.display())),
     }
 }
 
@@ -0,0 +1,71 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use nix::{errno::Errno, sys::signal::kill, unistd::Pid};
+use tracing::{info, warn};
+
+/// A file containing the PID of the bus process.
+#[derive(Debug)]
+pub(super) struct Pidfile {
+    path: PathBuf,
+}
+
+impl Pidfile {
+    /// Write our PID to `path`.
+    ///
+    /// Fails if `path` already contains the PID of a running process. PIDs of processes that are
+    /// gone are replaced.
+    pub(super) fn create(path: &Path) -> Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(contents) => match contents.trim().parse::<i32>() {
+                Ok(pid) if pid > 0 && is_running(Pid::from_raw(pid)) => bail!(
+                    "`{}` says a bus is already running with PID {pid}.",
+                    path.display()
+                ),
+                _ => warn!("Replacing stale PID file `{}`.", path.display()),
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read `{}`", path.display()))
+            }
+        }
+
+        // Write to a temporary file first, so that no one ever sees a partially written file.
+        let pid = std::process::id();
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(format!(".{pid}.tmp"));
+        let tmp_path = PathBuf::from(tmp_path);
+        let res = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o644)
+            .open(&tmp_path)
+            .and_then(|mut file| writeln!(file, "{pid}"))
+            .and_then(|_| fs::rename(&tmp_path, path));
+        if let Err(e) = res {
+            let _ = fs::remove_file(&tmp_path);
+
+            return Err(e).with_context(|| format!("Failed to write `{}`", path.display()));
+        }
+        info!("Wrote PID file `{}`.", path.display());
+
+        Ok(Self {
+            path: path.to_path_buf(),
+        })
+    }
+
+    pub(super) fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+fn is_running(pid: Pid) -> bool {
+    // Signal 0 only checks if the process exists. If it's not ours, it's still running.
+    matches!(kill(pid, None), Ok(()) | Err(Errno::EPERM))
+}
@@ -0,0 +1,66 @@
+use anyhow::{bail, Context, Result};
+use nix::{
+    sys::resource::{getrlimit, setrlimit, Resource},
+    unistd::{setgid, setuid, Gid, Uid, User},
+};
+use tracing::{debug, info, warn};
+
+/// The number of file descriptors the reference implementation makes sure a system bus can use.
+const MIN_FD_LIMIT: u64 = 65536;
+
+/// Permanently switch to `user`, with only their primary group.
+///
+/// Nothing happens if we're already running as `user`.
+pub(super) fn drop_privileges(user: &User) -> Result<()> {
+    if Uid::current() == user.uid && Uid::effective() == user.uid {
+        debug!("Already running as `{}`.", user.name);
+
+        return Ok(());
+    }
+
+    // Only privileged processes can raise the hard limit, so this can't be done after switching.
+    raise_fd_limit();
+
+    #[cfg(not(target_os = "macos"))]
+    nix::unistd::setgroups(&[]).context("Failed to drop supplementary groups")?;
+    setgid(user.gid).with_context(|| format!("Failed to switch to group {}", user.gid))?;
+    setuid(user.uid).with_context(|| format!("Failed to switch to user `{}`", user.name))?;
+
+    // Paranoia: make sure there's no way back.
+    if user.uid != Uid::from_raw(0) && setuid(Uid::from_raw(0)).is_ok() {
+        bail!(
+            "Still able to regain root privileges after switching to `{}`.",
+            user.name
+        );
+    }
+    if Gid::effective() != user.gid {
+        bail!("Failed to switch to group {}.", user.gid);
+    }
+    info!("Switched to user `{}`.", user.name);
+
+    Ok(())
+}
+
+/// Make sure we can handle plenty of connections, as far as file descriptors are concerned.
+fn raise_fd_limit() {
+    let (soft, hard) = match getrlimit(Resource::RLIMIT_NOFILE) {
+        Ok(limits) => limits,
+        Err(e) => {
+            warn!("Failed to get the file descriptor limit: {e}");
+
+            return;
+        }
+    };
+    if soft >= MIN_FD_LIMIT {
+        return;
+    }
+
+    match setrlimit(
+        Resource::RLIMIT_NOFILE,
+        MIN_FD_LIMIT,
+        hard.max(MIN_FD_LIMIT),
+    ) {
+        Ok(()) => debug!("Raised file descriptor limit from {soft} to {MIN_FD_LIMIT}."),
+        Err(e) => warn!("Failed to raise file descriptor limit to {MIN_FD_LIMIT}: {e}"),
+    }
+}
@@ -0,0 +1,147 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use tracing::warn;
+
+use super::xml::LimitElement;
+
+/// Resource limits, as set by `<limit>` elements.
+///
+/// Defaults are the same as the reference implementation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Limits {
+    /// Total size in bytes of messages received from a single connection and not yet sent on.
+    pub max_incoming_bytes: usize,
+
+    /// Total number of file descriptors received from a single connection and not yet sent on.
+    pub max_incoming_unix_fds: usize,
+
+    /// Total size in bytes of messages queued up for a single connection.
+    pub max_outgoing_bytes: usize,
+
+    /// Total number of file descriptors queued up for a single connection.
+    pub max_outgoing_unix_fds: usize,
+
+    /// Maximum size of a single message, in bytes.
+    pub max_message_size: usize,
+
+    /// Maximum number of file descriptors in a single message.
+    pub max_message_unix_fds: usize,
+
+    /// How long an activated service has to claim its name before the activation fails.
+    pub service_start_timeout: Duration,
+
+    /// How long a connection has to authenticate before it's dropped.
+    pub auth_timeout: Duration,
+
+    /// How long a connection can hold on to file descriptors in a partially received message.
+    pub pending_fd_timeout: Duration,
+
+    /// Maximum number of connections that have finished authenticating.
+    pub max_completed_connections: usize,
+
+    /// Maximum number of connections that have not finished authenticating yet. Any more are
+    /// dropped right away.
+    pub max_incomplete_connections: usize,
+
+    /// Maximum number of connections that have finished authenticating as the same user.
+    pub max_connections_per_user: usize,
+
+    /// Maximum number of services being activated at the same time.
+    pub max_pending_service_starts: usize,
+
+    /// Maximum number of names a single connection can own or be queued for.
+    pub max_names_per_connection: usize,
+
+    /// Maximum number of match rules a single connection can add.
+    pub max_match_rules_per_connection: usize,
+
+    /// Maximum number of method calls from a single connection still awaiting a reply.
+    pub max_replies_per_connection: usize,
+
+    /// How long to wait for a reply to a method call before giving up on it. `None`, the
+    /// default, means forever. A negative value in the configuration means the same.
+    pub reply_timeout: Option<Duration>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_incoming_bytes: 127 * MEGABYTE,
+            max_incoming_unix_fds: 4 * DEFAULT_MESSAGE_UNIX_FDS,
+            max_outgoing_bytes: 127 * MEGABYTE,
+            max_outgoing_unix_fds: 4 * DEFAULT_MESSAGE_UNIX_FDS,
+            max_message_size: 32 * MEGABYTE,
+            max_message_unix_fds: DEFAULT_MESSAGE_UNIX_FDS,
+            service_start_timeout: Duration::from_millis(25000),
+            auth_timeout: Duration::from_millis(5000),
+            pending_fd_timeout: Duration::from_millis(150000),
+            max_completed_connections: 2048,
+            max_incomplete_connections: 64,
+            max_connections_per_user: 256,
+            max_pending_service_starts: 512,
+            max_names_per_connection: 512,
+            max_match_rules_per_connection: 512,
+            max_replies_per_connection: 128,
+            reply_timeout: None,
+        }
+    }
+}
+
+impl Limits {
+    /// Apply a `<limit>` element.
+    ///
+    /// Unknown limits are ignored with a warning, as they may be understood by other
+    /// implementations.
+    pub(super) fn set(&mut self, limit: &LimitElement) -> Result<()> {
+        let name = limit.name.as_str();
+        let value = limit.value.trim();
+        match name {
+            "max_incoming_bytes" => self.max_incoming_bytes = parse(name, value)?,
+            "max_incoming_unix_fds" => self.max_incoming_unix_fds = parse(name, value)?,
+            "max_outgoing_bytes" => self.max_outgoing_bytes = parse(name, value)?,
+            "max_outgoing_unix_fds" => self.max_outgoing_unix_fds = parse(name, value)?,
+            "max_message_size" => self.max_message_size = parse(name, value)?,
+            "max_message_unix_fds" => self.max_message_unix_fds = parse(name, value)?,
+            "service_start_timeout" => self.service_start_timeout = parse_timeout(name, value)?,
+            "auth_timeout" => self.auth_timeout = parse_timeout(name, value)?,
+            "pending_fd_timeout" => self.pending_fd_timeout = parse_timeout(name, value)?,
+            "max_completed_connections" => self.max_completed_connections = parse(name, value)?,
+            "max_incomplete_connections" => self.max_incomplete_connections = parse(name, value)?,
+            "max_connections_per_user" => self.max_connections_per_user = parse(name, value)?,
+            "max_pending_service_starts" => self.max_pending_service_starts = parse(name, value)?,
+            "max_names_per_connection" => self.max_names_per_connection = parse(name, value)?,
+            "max_match_rules_per_connection" => {
+                self.max_match_rules_per_connection = parse(name, value)?
+            }
+            "max_replies_per_connection" => self.max_replies_per_connection = parse(name, value)?,
+            "reply_timeout" => {
+                let millis: i64 = parse(name, value)?;
+                self.reply_timeout = u64::try_from(millis).ok().map(Duration::from_millis);
+            }
+            _ => warn!("Ignoring unknown limit `{name}`."),
+        }
+
+        Ok(())
+    }
+}
+
+const MEGABYTE: usize = 1024 * 1024;
+
+/// What the reference implementation allows in a single message by default.
+const DEFAULT_MESSAGE_UNIX_FDS: usize = 1024;
+
+fn parse<T>(name: &str, value: &str) -> Result<T>
+where
+    T: std::str::FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    value
+        .parse()
+        .with_context(|| format!("Invalid value `{value}` for limit `{name}`"))
+}
+
+/// Timeouts are given in milliseconds.
+fn parse_timeout(name: &str, value: &str) -> Result<Duration> {
+    parse(name, value).map(Duration::from_millis)
+}
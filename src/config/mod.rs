@@ -7,18 +7,25 @@ use std::{
 use anyhow::{Error, Result};
 use policy::OptionalPolicy;
 use serde::Deserialize;
-use zbus::{Address, AuthMechanism};
+use zbus::Address;
 
+mod limits;
+mod network;
 pub mod policy;
 pub mod rule;
 mod xml;
 
+pub use limits::Limits;
+pub use network::IpNetwork;
 pub use policy::Policy;
 pub use rule::{
     Access, ConnectOperation, NameOwnership, Operation, ReceiveOperation, SendOperation,
 };
+pub use xml::Sources;
 use xml::{Document, Element, TypeElement};
 
+use crate::{auth::Mechanism, bus::parse_address};
+
 /// The bus configuration.
 ///
 /// This is currently only loaded from the [XML configuration files] defined by the specification.
@@ -33,19 +40,24 @@ pub struct Config {
     pub allow_anonymous: bool,
 
     /// Lists permitted authorization mechanisms.
-    /// If this element doesn't exist, then all known mechanisms are allowed.
-    // TODO: warn when multiple `<auth>` elements are defined, as we only support one
-    // TODO: consider implementing `Deserialize` over in zbus crate, then removing this "skip..."
+    /// If this is empty, then all known mechanisms are allowed.
     #[serde(default, skip_deserializing)]
-    pub auth: Option<AuthMechanism>,
+    pub auth: Vec<Mechanism>,
 
     /// If `true`, the bus daemon becomes a real daemon (forks into the background, etc.).
     pub fork: bool,
 
     /// If `true`, the bus daemon keeps its original umask when forking.
     /// This may be useful to avoid affecting the behavior of child processes.
+    ///
+    /// This also leaves the mode of UNIX socket files to the umask. Otherwise, they are made
+    /// accessible to everyone on the system bus and only to the bus owner on other buses.
     pub keep_umask: bool,
 
+    /// Resource limits.
+    #[serde(default, skip_deserializing)]
+    pub limits: Limits,
+
     /// Address that the bus should listen on.
     /// The address is in the standard D-Bus format that contains a transport name plus possible
     /// parameters/options.
@@ -68,6 +80,13 @@ pub struct Config {
     /// Specifies the setuid helper that is used to launch system daemons with an alternate user.
     pub servicehelper: Option<PathBuf>,
 
+    /// Addresses of the clients allowed to connect when listening on TCP. If this is empty,
+    /// clients can connect from anywhere.
+    ///
+    /// This is a busd extension, which can't be set from the XML configuration.
+    #[serde(default, skip_deserializing)]
+    pub tcp_allowed_clients: Vec<IpNetwork>,
+
     /// If `true`, the bus daemon will log to syslog.
     pub syslog: bool,
 
@@ -91,7 +110,10 @@ impl TryFrom<Document> for Config {
             match element {
                 Element::AllowAnonymous => config.allow_anonymous = true,
                 Element::Auth(auth) => {
-                    config.auth = Some(AuthMechanism::from_str(&auth)?);
+                    let mechanism = Mechanism::from_str(&auth)?;
+                    if !config.auth.contains(&mechanism) {
+                        config.auth.push(mechanism);
+                    }
                 }
                 Element::Fork => config.fork = true,
                 Element::Include(_) => {
@@ -101,11 +123,9 @@ impl TryFrom<Document> for Config {
                     // NO-OP: removed during `Document::resolve_includedirs`
                 }
                 Element::KeepUmask => config.keep_umask = true,
-                Element::Limit => {
-                    // NO-OP: deprecated and ignored
-                }
+                Element::Limit(limit) => config.limits.set(&limit)?,
                 Element::Listen(listen) => {
-                    config.listen = Some(Address::from_str(&listen)?);
+                    config.listen = Some(parse_address(&listen)?);
                 }
                 Element::Pidfile(p) => config.pidfile = Some(p),
                 Element::Policy(pe) => {
@@ -168,8 +188,17 @@ impl Config {
     }
 
     pub fn read_file(file_path: impl AsRef<Path>) -> Result<Self> {
+        Self::read_file_with_sources(file_path).map(|(config, _)| config)
+    }
+
+    /// Same as [`Config::read_file`] but also returns the files and `<includedir>` directories
+    /// the configuration was read from.
+    pub fn read_file_with_sources(file_path: impl AsRef<Path>) -> Result<(Self, Sources)> {
         // TODO: error message should contain file path to missing `<include>`
-        Document::read_file(&file_path)?.try_into()
+        let doc = Document::read_file(&file_path)?;
+        let sources = doc.sources.clone();
+
+        Ok((doc.try_into()?, sources))
     }
 }
 
@@ -218,6 +247,8 @@ fn xdg_data_dirs() -> Vec<PathBuf> {
 
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
     use rule::{
         Access, ConnectOperation, NameOwnership, Operation, ReceiveOperation, SendOperation,
     };
@@ -286,7 +317,7 @@ mod tests {
         assert_eq!(
             config,
             Config {
-                auth: Some(AuthMechanism::External),
+                auth: vec![Mechanism::Anonymous, Mechanism::External],
                 ..Default::default()
             }
         );
@@ -302,6 +333,84 @@ mod tests {
         "#;
 
         Config::parse(input).expect("should parse XML input");
+
+        let input = r#"<!DOCTYPE busconfig PUBLIC "-//freedesktop//DTD D-Bus Bus Configuration 1.0//EN"
+        "http://www.freedesktop.org/standards/dbus/1.0/busconfig.dtd">
+        <busconfig>
+            <limit name="auth_timeout">5000</limit>
+            <limit name="max_incomplete_connections">10</limit>
+            <limit name="max_match_rules_per_connection"> 20 </limit>
+            <limit name="reply_timeout">-1</limit>
+            <limit name="max_containers">1</limit>
+        </busconfig>
+        "#;
+
+        let config = Config::parse(input).expect("should parse XML input");
+
+        assert_eq!(
+            config.limits,
+            Limits {
+                auth_timeout: Duration::from_secs(5),
+                max_incomplete_connections: 10,
+                max_match_rules_per_connection: 20,
+                reply_timeout: None,
+                ..Default::default()
+            }
+        );
+
+        let input = r#"<!DOCTYPE busconfig PUBLIC "-//freedesktop//DTD D-Bus Bus Configuration 1.0//EN"
+        "http://www.freedesktop.org/standards/dbus/1.0/busconfig.dtd">
+        <busconfig>
+            <limit name="reply_timeout">300000</limit>
+        </busconfig>
+        "#;
+
+        let config = Config::parse(input).expect("should parse XML input");
+
+        assert_eq!(config.limits.reply_timeout, Some(Duration::from_secs(300)));
+
+        let input = r#"<!DOCTYPE busconfig PUBLIC "-//freedesktop//DTD D-Bus Bus Configuration 1.0//EN"
+        "http://www.freedesktop.org/standards/dbus/1.0/busconfig.dtd">
+        <busconfig>
+            <limit name="max_incomplete_connections">many</limit>
+        </busconfig>
+        "#;
+
+        Config::parse(input).expect_err("should fail on invalid limit value");
+
+        let input = r#"<!DOCTYPE busconfig PUBLIC "-//freedesktop//DTD D-Bus Bus Configuration 1.0//EN"
+        "http://www.freedesktop.org/standards/dbus/1.0/busconfig.dtd">
+        <busconfig>
+            <limit name="max_message_size">-1</limit>
+        </busconfig>
+        "#;
+
+        Config::parse(input).expect_err("should fail on negative size limit");
+    }
+
+    #[test]
+    fn ip_network_parse_ok() {
+        let network = IpNetwork::from_str("192.168.1.0/24").expect("should parse network");
+        assert!(network.contains(&"192.168.1.42".parse().unwrap()));
+        assert!(network.contains(&"::ffff:192.168.1.42".parse().unwrap()));
+        assert!(!network.contains(&"192.168.2.1".parse().unwrap()));
+        assert!(!network.contains(&"fd00::1".parse().unwrap()));
+        assert_eq!(network.to_string(), "192.168.1.0/24");
+
+        let network = IpNetwork::from_str("fd00::/8").expect("should parse network");
+        assert!(network.contains(&"fd12::1".parse().unwrap()));
+        assert!(!network.contains(&"fe80::1".parse().unwrap()));
+
+        let network = IpNetwork::from_str("10.0.0.1").expect("should parse address");
+        assert!(network.contains(&"10.0.0.1".parse().unwrap()));
+        assert!(!network.contains(&"10.0.0.2".parse().unwrap()));
+
+        assert!(IpNetwork::from_str("0.0.0.0/0")
+            .unwrap()
+            .contains(&"1.2.3.4".parse().unwrap()));
+
+        IpNetwork::from_str("10.0.0.0/33").expect_err("should fail on long prefix");
+        IpNetwork::from_str("localhost").expect_err("should fail on host name");
     }
 
     #[test]
@@ -358,7 +467,7 @@ mod tests {
         assert_eq!(
             config,
             Config {
-                auth: Some(AuthMechanism::External),
+                auth: vec![Mechanism::Anonymous, Mechanism::External],
                 listen: Some(
                     Address::from_str("tcp:host=localhost,port=1234")
                         .expect("should parse address")
@@ -0,0 +1,87 @@
+use std::{fmt, net::IpAddr, str::FromStr};
+
+use anyhow::{anyhow, bail, Error, Result};
+
+/// A range of IP addresses, in CIDR notation (e.g `192.168.1.0/24` or `fd00::/8`).
+///
+/// A single address is a range of its own.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IpNetwork {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpNetwork {
+    pub fn new(addr: IpAddr, prefix_len: u8) -> Result<Self> {
+        let max_len = max_prefix_len(&addr);
+        if prefix_len > max_len {
+            bail!("Prefix length {prefix_len} is longer than {max_len} bits.");
+        }
+
+        Ok(Self { addr, prefix_len })
+    }
+
+    /// Whether `addr` is in this range.
+    ///
+    /// IPv4 addresses mapped to IPv6 (as seen by dual-stack listeners) match IPv4 ranges.
+    pub fn contains(&self, addr: &IpAddr) -> bool {
+        let addr = match addr {
+            IpAddr::V6(v6) => v6
+                .to_ipv4_mapped()
+                .map(IpAddr::V4)
+                .unwrap_or(IpAddr::V6(*v6)),
+            v4 => *v4,
+        };
+
+        match (self.addr, addr) {
+            (IpAddr::V4(net), IpAddr::V4(addr)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - u32::from(self.prefix_len))
+                    .unwrap_or(0);
+
+                u32::from(net) & mask == u32::from(addr) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(addr)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - u32::from(self.prefix_len))
+                    .unwrap_or(0);
+
+                u128::from(net) & mask == u128::from(addr) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpNetwork {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (addr, prefix_len) = match s.split_once('/') {
+            Some((addr, prefix_len)) => (addr, Some(prefix_len)),
+            None => (s, None),
+        };
+        let addr = IpAddr::from_str(addr).map_err(|_| anyhow!("Invalid IP address `{addr}`."))?;
+        let prefix_len = match prefix_len {
+            Some(len) => len
+                .parse()
+                .map_err(|_| anyhow!("Invalid prefix length `{len}`."))?,
+            None => max_prefix_len(&addr),
+        };
+
+        Self::new(addr, prefix_len)
+    }
+}
+
+impl fmt::Display for IpNetwork {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+fn max_prefix_len(addr: &IpAddr) -> u8 {
+    match addr {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    }
+}
@@ -23,6 +23,18 @@ pub struct Document {
     #[serde(rename = "$value", default)]
     pub busconfig: Vec<Element>,
     file_path: Option<PathBuf>,
+    /// The files and `<includedir>` directories this document was read from.
+    #[serde(skip)]
+    pub sources: Sources,
+}
+
+/// The files and `<includedir>` directories a configuration was read from.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Sources {
+    /// The main file and every file it includes, directly or not.
+    pub files: Vec<PathBuf>,
+    /// The directories given by `<includedir>` elements that could be read.
+    pub dirs: Vec<PathBuf>,
 }
 
 impl FromStr for Document {
@@ -39,6 +51,7 @@ impl Document {
 
         let mut doc = Document::from_str(&text)?;
         doc.file_path = Some(file_path.as_ref().to_path_buf());
+        doc.sources.files.push(file_path.as_ref().to_path_buf());
         doc.resolve_includedirs()?.resolve_includes()
     }
 
@@ -47,11 +60,13 @@ impl Document {
         let Document {
             busconfig,
             file_path,
+            sources,
         } = self;
 
         let mut doc = Document {
             busconfig: vec![],
             file_path: None,
+            sources,
         };
 
         for el in busconfig {
@@ -72,6 +87,7 @@ impl Document {
                     };
                     match read_dir(&dir_path) {
                         Ok(ok) => {
+                            doc.sources.dirs.push(dir_path.clone());
                             for entry in ok {
                                 let path = entry?.path();
                                 if path.extension() == Some(&OsString::from("conf"))
@@ -109,11 +125,13 @@ impl Document {
         let Document {
             busconfig,
             file_path,
+            sources,
         } = self;
 
         let mut doc = Document {
             busconfig: vec![],
             file_path: None,
+            sources,
         };
 
         for el in busconfig {
@@ -160,6 +178,8 @@ impl Document {
                         }
                     };
                     doc.busconfig.append(&mut included.busconfig);
+                    doc.sources.files.append(&mut included.sources.files);
+                    doc.sources.dirs.append(&mut included.sources.dirs);
                 }
                 _ => doc.busconfig.push(el),
             }
@@ -197,7 +217,7 @@ pub enum Element {
     Includedir(PathBuf),
     KeepUmask,
     Listen(String),
-    Limit,
+    Limit(LimitElement),
     Pidfile(PathBuf),
     Policy(PolicyElement),
     Servicedir(PathBuf),
@@ -325,6 +345,14 @@ pub enum RuleElement {
     Deny(RuleAttributes),
 }
 
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct LimitElement {
+    #[serde(rename = "@name")]
+    pub name: String,
+    #[serde(rename = "$text")]
+    pub value: String,
+}
+
 #[derive(Clone, Debug, Deserialize, PartialEq)]
 pub struct TypeElement {
     #[serde(rename = "$text")]
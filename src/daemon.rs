@@ -0,0 +1,144 @@
+//! Running in the background, the way the reference implementation does it with `<fork/>`.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    os::fd::{AsRawFd, RawFd},
+    process::exit,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use nix::{
+    sys::{
+        stat::{umask, Mode},
+        wait::waitpid,
+    },
+    unistd::{
+        chdir, close, dup2_stderr, dup2_stdin, dup2_stdout, fork, pipe, setsid, ForkResult, Pid,
+    },
+};
+
+/// The outcome of [`daemonize`].
+#[derive(Debug)]
+pub enum Daemonized {
+    /// We're the original process and the daemon is now listening.
+    Parent {
+        /// The process ID of the daemon.
+        pid: Pid,
+        /// The address the daemon is listening on.
+        address: String,
+    },
+    /// We're the daemon.
+    Daemon(Readiness),
+}
+
+/// Lets the daemon tell the original process it's done setting up, or that it failed to.
+#[derive(Debug)]
+pub struct Readiness {
+    pipe: File,
+}
+
+/// Become a daemon.
+///
+/// This forks twice, with a new session in between, so that the daemon is detached from the
+/// terminal and isn't a session leader. The daemon then moves to `/`, resets its umask to `022`
+/// unless `keep_umask` is set, and its standard input and outputs are redirected to `/dev/null`.
+/// File descriptors in `parent_fds` are only for the original process to use, so the daemon closes
+/// them. Whoever reads from them can then tell when the original process is done.
+///
+/// The original process waits until the daemon reports back through [`Readiness`] and gets
+/// [`Daemonized::Parent`] if the daemon is ready. If it failed, the error is returned instead.
+///
+/// This must be called while the process has a single thread, so before any async runtime is
+/// started.
+pub fn daemonize(keep_umask: bool, parent_fds: &[RawFd]) -> Result<Daemonized> {
+    let (read, write) = pipe().context("Failed to create a pipe")?;
+
+    // SAFETY: We don't have other threads at this point so we can do whatever we want afterwards.
+    match unsafe { fork() }.context("Failed to fork")? {
+        ForkResult::Parent { child } => {
+            drop(write);
+            // The intermediate process exits right away.
+            waitpid(child, None)?;
+
+            return wait_for_daemon(File::from(read));
+        }
+        ForkResult::Child => drop(read),
+    }
+
+    // A new session, so we don't get a controlling terminal.
+    setsid().context("Failed to create a new session")?;
+    // SAFETY: Same as above.
+    match unsafe { fork() }.context("Failed to fork")? {
+        // Leaving the session leader behind, so we can't acquire a controlling terminal anymore.
+        ForkResult::Parent { .. } => exit(0),
+        ForkResult::Child => (),
+    }
+
+    chdir("/").context("Failed to change directory to `/`")?;
+    if !keep_umask {
+        umask(Mode::from_bits_truncate(0o022));
+    }
+    let dev_null = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/null")
+        .context("Failed to open `/dev/null`")?;
+    dup2_stdin(&dev_null)?;
+    dup2_stdout(&dev_null)?;
+    dup2_stderr(&dev_null)?;
+    drop(dev_null);
+    for &fd in parent_fds {
+        // Only if they are still what they were when we forked.
+        if fd > 2 && fd != write.as_raw_fd() {
+            let _ = close(fd);
+        }
+    }
+
+    Ok(Daemonized::Daemon(Readiness {
+        pipe: File::from(write),
+    }))
+}
+
+impl Readiness {
+    /// Tell the original process we're listening on `address`.
+    pub fn ready(mut self, address: &str) -> Result<()> {
+        writeln!(self.pipe, "READY {} {address}", std::process::id())
+            .context("Failed to report readiness")
+    }
+
+    /// Tell the original process we failed to start.
+    pub fn failed(mut self, error: &anyhow::Error) {
+        // There's not much we can do if this fails.
+        let _ = writeln!(
+            self.pipe,
+            "ERROR {}",
+            format!("{error:#}").replace('\n', " ")
+        );
+    }
+}
+
+fn wait_for_daemon(pipe: File) -> Result<Daemonized> {
+    let mut line = String::new();
+    BufReader::new(pipe)
+        .read_line(&mut line)
+        .context("Failed to wait for the daemon")?;
+    let line = line.trim_end();
+
+    if let Some(error) = line.strip_prefix("ERROR ") {
+        bail!("Daemon failed to start: {error}");
+    }
+    let (pid, address) = line
+        .strip_prefix("READY ")
+        .and_then(|ready| ready.split_once(' '))
+        .ok_or_else(|| anyhow!("Daemon exited before it was ready."))?;
+    let pid = pid
+        .parse()
+        .map(Pid::from_raw)
+        .with_context(|| format!("Daemon reported an invalid PID `{pid}`"))?;
+
+    Ok(Daemonized::Parent {
+        pid,
+        address: address.to_string(),
+    })
+}
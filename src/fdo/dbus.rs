@@ -19,22 +19,24 @@ use zbus::{
 };
 
 use super::msg_sender;
-use crate::{peer::Peer, peers::Peers};
+use crate::{bus::LiveConfig, peer::Peer, peers::Peers};
 
 #[derive(Debug)]
 pub struct DBus {
     peers: Weak<Peers>,
     guid: OwnedGuid,
+    config: Arc<LiveConfig>,
 }
 
 impl DBus {
     pub const PATH: &'static str = "/org/freedesktop/DBus";
     pub const INTERFACE: &'static str = "org.freedesktop.DBus";
 
-    pub fn new(peers: Arc<Peers>, guid: OwnedGuid) -> Self {
+    pub fn new(peers: Arc<Peers>, guid: OwnedGuid, config: Arc<LiveConfig>) -> Self {
         Self {
             peers: Arc::downgrade(&peers),
             guid,
+            config,
         }
     }
 
@@ -116,11 +118,20 @@ impl DBus {
     ) -> Result<RequestNameReply> {
         let unique_name = msg_sender(&hdr);
         let peers = self.peers()?;
-        let (reply, name_owner_changed) = peers
-            .name_registry_mut()
-            .await
-            .request_name(name, unique_name.clone(), flags)
-            .await;
+        let max = self.config.limits().max_names_per_connection;
+        let (reply, name_owner_changed) = {
+            let mut name_registry = peers.name_registry_mut().await;
+            let claims = name_registry.claims(unique_name.as_ref());
+            if claims.len() >= max && !claims.iter().any(|claimed| *claimed == name) {
+                return Err(Error::LimitsExceeded(format!(
+                    "Connection `{unique_name}` owns or is queued for too many names (max {max})"
+                )));
+            }
+
+            name_registry
+                .request_name(name, unique_name.clone(), flags)
+                .await
+        };
         if let Some(changed) = name_owner_changed {
             peers
                 .notify_name_changes(changed)
@@ -179,15 +190,9 @@ impl DBus {
         rule: OwnedMatchRule,
         #[zbus(header)] hdr: message::Header<'_>,
     ) -> Result<()> {
-        self.call_mut_on_peer(
-            move |peer| {
-                peer.add_match_rule(rule);
-
-                Ok(())
-            },
-            hdr,
-        )
-        .await
+        let max = self.config.limits().max_match_rules_per_connection;
+        self.call_mut_on_peer(move |peer| peer.add_match_rule(rule, max), hdr)
+            .await
     }
 
     /// Removes the first rule that matches.
@@ -343,11 +348,12 @@ impl DBus {
     }
 
     /// Reload server configuration.
-    fn reload_config(&self) -> Result<()> {
-        // TODO: Implement when we support configuration.
-        Err(Error::Failed(
-            "No server configuration to reload.".to_string(),
-        ))
+    async fn reload_config(&self) -> Result<()> {
+        self.config
+            .reload()
+            .await
+            .map(|_| ())
+            .map_err(|e| Error::Failed(format!("{e:#}")))
     }
 
     /// Easter egg method.
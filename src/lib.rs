@@ -1,8 +1,11 @@
+pub mod auth;
 pub mod bus;
 pub mod config;
+pub mod daemon;
 pub mod fdo;
 pub mod match_rules;
 pub mod name_registry;
 pub mod peer;
 pub mod peers;
+pub mod sd_notify;
 pub mod tracing_subscriber;
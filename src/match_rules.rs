@@ -60,8 +60,16 @@ impl MatchRules {
         ret
     }
 
-    pub fn add(&mut self, rule: OwnedMatchRule) {
+    /// Add a rule, unless there are already `max` others.
+    pub fn add(&mut self, rule: OwnedMatchRule, max: usize) -> zbus::fdo::Result<()> {
+        if self.0.len() >= max && !self.0.contains(&rule) {
+            return Err(zbus::fdo::Error::LimitsExceeded(format!(
+                "Connection has too many match rules (max {max})"
+            )));
+        }
         self.0.insert(rule);
+
+        Ok(())
     }
 
     /// Remove the first rule that matches.
@@ -155,9 +155,22 @@ impl NameRegistry {
     }
 
     pub async fn release_all(&mut self, owner: UniqueName<'_>) -> Vec<NameOwnerChanged> {
-        // Find all names registered and queued by the given owner.
-        let names: Vec<_> = self
-            .names
+        let names = self.claims(owner.clone());
+        // Now release our claim or waiting list tickets from all these names.
+        let mut all_changed = vec![];
+        for name in names {
+            let (_, changed) = self.release_name(name.inner().clone(), owner.clone()).await;
+            if let Some(changed) = changed {
+                all_changed.push(changed);
+            }
+        }
+
+        all_changed
+    }
+
+    /// All names owned or queued for by `owner`.
+    pub fn claims(&self, owner: UniqueName<'_>) -> Vec<OwnedWellKnownName> {
+        self.names
             .iter()
             .filter_map(|(name, entry)| {
                 if *entry.owner.unique_name == owner
@@ -171,17 +184,7 @@ impl NameRegistry {
                     None
                 }
             })
-            .collect();
-        // Now release our claim or waiting list tickets from all these names.
-        let mut all_changed = vec![];
-        for name in names {
-            let (_, changed) = self.release_name(name.inner().clone(), owner.clone()).await;
-            if let Some(changed) = changed {
-                all_changed.push(changed);
-            }
-        }
-
-        all_changed
+            .collect()
     }
 
     pub fn lookup(&self, name: WellKnownName) -> Option<OwnedUniqueName> {
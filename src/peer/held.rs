@@ -0,0 +1,70 @@
+use std::{io, mem, os::fd::OwnedFd};
+
+use async_trait::async_trait;
+use tokio::sync::oneshot;
+use zbus::{
+    connection::socket::{BoxedSplit, ReadHalf},
+    fdo::ConnectionCredentials,
+    AuthMechanism, Message,
+};
+
+/// Hold off receiving messages on `socket` until the returned sender is used or dropped.
+///
+/// The connection starts reading as soon as it's built but any message received before something
+/// listens on its message stream is lost. Peers typically send `Hello` right after the handshake,
+/// so that would be lost without this.
+pub(super) fn hold_reads(mut socket: BoxedSplit) -> (BoxedSplit, oneshot::Sender<()>) {
+    let (tx, rx) = oneshot::channel();
+    let read = mem::replace(socket.read_mut(), Box::new(Detached));
+    *socket.read_mut() = Box::new(Held {
+        read,
+        release: Some(rx),
+    });
+
+    (socket, tx)
+}
+
+/// A read half that waits for a signal before receiving anything.
+#[derive(Debug)]
+struct Held {
+    read: Box<dyn ReadHalf>,
+    release: Option<oneshot::Receiver<()>>,
+}
+
+#[async_trait]
+impl ReadHalf for Held {
+    async fn receive_message(
+        &mut self,
+        seq: u64,
+        already_received_bytes: &mut Vec<u8>,
+        already_received_fds: &mut Vec<OwnedFd>,
+    ) -> zbus::Result<Message> {
+        if let Some(release) = self.release.take() {
+            // Whether the sender was used or dropped, there's no reason to wait anymore.
+            let _ = release.await;
+        }
+
+        self.read
+            .receive_message(seq, already_received_bytes, already_received_fds)
+            .await
+    }
+
+    fn can_pass_unix_fd(&self) -> bool {
+        self.read.can_pass_unix_fd()
+    }
+
+    async fn peer_credentials(&mut self) -> io::Result<ConnectionCredentials> {
+        self.read.peer_credentials().await
+    }
+
+    fn auth_mechanism(&self) -> AuthMechanism {
+        self.read.auth_mechanism()
+    }
+}
+
+/// Stand-in for the read half while it's being wrapped. Never used for any I/O.
+#[derive(Debug)]
+struct Detached;
+
+#[async_trait]
+impl ReadHalf for Detached {}
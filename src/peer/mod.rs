@@ -1,52 +1,90 @@
 mod stream;
 use event_listener::{Event, EventListener};
 pub use stream::*;
+mod held;
 mod monitor;
 pub use monitor::*;
 
 use anyhow::Result;
+use tokio::sync::oneshot;
 use tracing::trace;
 use zbus::{
-    connection::{self, socket::BoxedSplit},
+    connection::{
+        self,
+        socket::{BoxedSplit, Channel},
+    },
     names::{BusName, OwnedUniqueName},
-    AuthMechanism, Connection, OwnedGuid, OwnedMatchRule,
+    Connection, OwnedGuid, OwnedMatchRule,
 };
 
-use crate::{fdo, match_rules::MatchRules, name_registry::NameRegistry};
+use crate::{auth, fdo, match_rules::MatchRules, name_registry::NameRegistry};
 
 /// A peer connection.
 #[derive(Debug)]
 pub struct Peer {
     conn: Connection,
     unique_name: OwnedUniqueName,
+    uid: Option<u32>,
     match_rules: MatchRules,
     greeted: bool,
     canceled_event: Event,
+    reads_held: Option<oneshot::Sender<()>>,
 }
 
 impl Peer {
+    /// A peer that went through the authentication handshake on `socket`.
     pub async fn new(
         guid: OwnedGuid,
         id: usize,
-        socket: BoxedSplit,
-        auth_mechanism: AuthMechanism,
+        mut socket: BoxedSplit,
+        authenticated: auth::Authenticated,
     ) -> Result<Self> {
-        let unique_name = OwnedUniqueName::try_from(format!(":busd.{id}")).unwrap();
-        let conn = connection::Builder::socket(socket)
-            .server(guid)?
+        if let (auth::Mechanism::CookieSha1, Some(uid)) =
+            (authenticated.mechanism, authenticated.uid)
+        {
+            // There are no credentials to go with the socket (typically TCP), so vouch for the peer
+            // being who it authenticated as.
+            socket = auth::override_uid(socket, uid);
+        }
+        let (socket, reads_held) = held::hold_reads(socket);
+        let conn = connection::Builder::authenticated_socket(socket, guid)?
             .p2p()
-            .auth_mechanism(auth_mechanism)
             .build()
             .await?;
         trace!("created: {:?}", conn);
 
-        Ok(Self {
+        let mut peer = Self::for_conn(conn, id);
+        peer.uid = authenticated.uid;
+        peer.reads_held = Some(reads_held);
+
+        Ok(peer)
+    }
+
+    /// A peer in the same process as the bus.
+    ///
+    /// There is nothing to authenticate in this case, so no handshake happens.
+    pub async fn new_in_process(guid: OwnedGuid, id: usize, socket: Channel) -> Result<Self> {
+        let conn = connection::Builder::authenticated_socket(socket, guid)?
+            .p2p()
+            .build()
+            .await?;
+        trace!("created in-process: {:?}", conn);
+
+        Ok(Self::for_conn(conn, id))
+    }
+
+    fn for_conn(conn: Connection, id: usize) -> Self {
+        let unique_name = OwnedUniqueName::try_from(format!(":busd.{id}")).unwrap();
+
+        Self {
             conn,
             unique_name,
+            uid: None,
             match_rules: MatchRules::default(),
             greeted: false,
             canceled_event: Event::new(),
-        })
+            reads_held: None,
+        }
     }
 
     // This the the bus itself, serving the FDO D-Bus API.
@@ -56,9 +94,11 @@ impl Peer {
         Self {
             conn,
             unique_name,
+            uid: None,
             match_rules: MatchRules::default(),
             greeted: true,
             canceled_event: Event::new(),
+            reads_held: None,
         }
     }
 
@@ -70,8 +110,23 @@ impl Peer {
         &self.conn
     }
 
-    pub fn stream(&self) -> Stream {
-        Stream::for_peer(self)
+    /// The user the peer authenticated as, if any.
+    ///
+    /// In-process and anonymous peers have none.
+    pub fn uid(&self) -> Option<u32> {
+        self.uid
+    }
+
+    /// The stream of messages from the peer.
+    ///
+    /// No messages are received from the peer before this is first called.
+    pub fn stream(&mut self) -> Stream {
+        let stream = Stream::for_peer(self);
+        if let Some(reads_held) = self.reads_held.take() {
+            let _ = reads_held.send(());
+        }
+
+        stream
     }
 
     pub fn listen_cancellation(&self) -> EventListener {
@@ -86,8 +141,9 @@ impl Peer {
             || self.match_rules.matches(msg, name_registry)
     }
 
-    pub fn add_match_rule(&mut self, rule: OwnedMatchRule) {
-        self.match_rules.add(rule);
+    /// Add a match rule, unless the peer already has `max` others.
+    pub fn add_match_rule(&mut self, rule: OwnedMatchRule, max: usize) -> zbus::fdo::Result<()> {
+        self.match_rules.add(rule, max)
     }
 
     /// Remove the first rule that matches.
@@ -16,6 +16,7 @@ use super::Peer;
 pub struct Monitor {
     conn: Connection,
     unique_name: OwnedUniqueName,
+    uid: Option<u32>,
     match_rules: MatchRules,
 }
 
@@ -28,6 +29,11 @@ impl Monitor {
         &self.unique_name
     }
 
+    /// Same as [`Peer::uid`].
+    pub fn uid(&self) -> Option<u32> {
+        self.uid
+    }
+
     /// # Panics
     ///
     /// Same as [`MatchRules::matches`].
@@ -60,6 +66,7 @@ impl Monitor {
         Self {
             conn: peer.conn().clone(),
             unique_name: peer.unique_name().clone(),
+            uid: peer.uid(),
             match_rules,
         }
     }
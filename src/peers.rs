@@ -6,20 +6,31 @@ use futures_util::{
 };
 use std::{
     collections::BTreeMap,
+    mem,
     ops::{Deref, DerefMut},
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+use tokio::{
+    spawn,
+    sync::RwLock,
+    task::JoinSet,
+    time::{timeout_at, Instant},
 };
-use tokio::{spawn, sync::RwLock};
 use tracing::{debug, trace, warn};
 use zbus::{
-    connection::socket::BoxedSplit,
+    connection::socket::{BoxedSplit, Channel},
     message,
     names::{BusName, OwnedUniqueName, UniqueName},
     zvariant::Optional,
-    AuthMechanism, Message, OwnedGuid,
+    Message, OwnedGuid,
 };
 
 use crate::{
+    auth,
+    config::Limits,
     fdo,
     match_rules::MatchRules,
     name_registry::{NameOwnerChanged, NameRegistry},
@@ -31,6 +42,16 @@ pub struct Peers {
     peers: RwLock<BTreeMap<OwnedUniqueName, Peer>>,
     monitors: RwLock<BTreeMap<OwnedUniqueName, Monitor>>,
     name_registry: RwLock<NameRegistry>,
+    shut_down: AtomicBool,
+}
+
+/// How many peers are connected to the bus.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConnectionCounts {
+    /// All peers, monitors included, but not the bus itself.
+    pub total: usize,
+    /// Peers per user they authenticated as. Anonymous and in-process peers aren't counted here.
+    pub per_user: BTreeMap<u32, usize>,
 }
 
 impl Peers {
@@ -41,6 +62,7 @@ impl Peers {
             peers: RwLock::new(BTreeMap::new()),
             monitors: RwLock::new(BTreeMap::new()),
             name_registry: RwLock::new(name_registry),
+            shut_down: AtomicBool::new(false),
         })
     }
 
@@ -49,33 +71,141 @@ impl Peers {
         guid: &OwnedGuid,
         id: usize,
         socket: BoxedSplit,
-        auth_mechanism: AuthMechanism,
+        authenticated: auth::Authenticated,
+        connect_policy: &auth::ConnectPolicy,
+        limits: &Limits,
     ) -> Result<()> {
-        let mut peers = self.peers_mut().await;
-        let peer = Peer::new(guid.clone(), id, socket, auth_mechanism).await?;
-        let unique_name = peer.unique_name().clone();
-        match peers.get(&unique_name) {
-            Some(peer) => panic!(
-                "Unique name `{}` re-used. We're in deep trouble if this happens",
-                peer.unique_name()
-            ),
-            None => {
-                let peer_stream = peer.stream();
-                let listener = peer.listen_cancellation();
-                tokio::spawn(
-                    self.clone()
-                        .serve_peer(peer_stream, listener, unique_name.clone()),
+        // Anonymous peers are only allowed in if the configuration says so, in which case
+        // `auth_mechanisms` includes ANONYMOUS.
+        if let Some(uid) = authenticated.uid {
+            if !connect_policy.allows(uid) {
+                bail!(
+                    "User {uid} is not allowed to connect to a bus owned by {}.",
+                    connect_policy.owner()
                 );
-                peers.insert(unique_name.clone(), peer);
             }
         }
 
+        // Only lock the peers once the peer is ready, so that slow peers don't hold up the bus.
+        let peer = Peer::new(guid.clone(), id, socket, authenticated).await?;
+        let mut peers = self.peers_mut().await;
+        // Checked while holding the lock, so that concurrent connections can't all get in. The
+        // peer hasn't been served yet, so dropping it closes the connection before `Hello`.
+        let counts = self.count(&peers).await;
+        let max = limits.max_completed_connections;
+        if counts.total >= max {
+            bail!("The maximum number of active connections ({max}) has been reached.");
+        }
+        if let Some(uid) = peer.uid() {
+            let max = limits.max_connections_per_user;
+            if counts.per_user.get(&uid).copied().unwrap_or(0) >= max {
+                bail!("The maximum number of active connections for UID {uid} ({max}) has been reached.");
+            }
+        }
+        self.insert(&mut peers, peer);
+
+        Ok(())
+    }
+
+    pub async fn add_in_process(
+        self: &Arc<Self>,
+        guid: &OwnedGuid,
+        id: usize,
+        socket: Channel,
+    ) -> Result<()> {
+        let peer = Peer::new_in_process(guid.clone(), id, socket).await?;
+        self.insert(&mut *self.peers_mut().await, peer);
+
         Ok(())
     }
 
     pub async fn add_us(self: &Arc<Self>, conn: zbus::Connection) {
-        let mut peers = self.peers_mut().await;
         let peer = Peer::new_us(conn).await;
+        self.insert(&mut *self.peers_mut().await, peer);
+    }
+
+    pub async fn peers(&self) -> impl Deref<Target = BTreeMap<OwnedUniqueName, Peer>> + '_ {
+        self.peers.read().await
+    }
+
+    pub async fn peers_mut(&self) -> impl DerefMut<Target = BTreeMap<OwnedUniqueName, Peer>> + '_ {
+        self.peers.write().await
+    }
+
+    /// The number of peers currently connected.
+    pub async fn connection_counts(&self) -> ConnectionCounts {
+        self.count(&*self.peers().await).await
+    }
+
+    async fn count(&self, peers: &BTreeMap<OwnedUniqueName, Peer>) -> ConnectionCounts {
+        let monitors = self.monitors.read().await;
+        let mut counts = ConnectionCounts::default();
+        let uids = peers
+            .values()
+            .filter(|peer| peer.unique_name().as_str() != fdo::BUS_NAME)
+            .map(Peer::uid)
+            .chain(monitors.values().map(Monitor::uid));
+        for uid in uids {
+            counts.total += 1;
+            if let Some(uid) = uid {
+                *counts.per_user.entry(uid).or_default() += 1;
+            }
+        }
+
+        counts
+    }
+
+    /// Disconnect all peers and refuse new ones.
+    ///
+    /// Messages already being sent to peers go out first, unless that takes until `deadline`.
+    pub async fn shutdown(&self, deadline: Instant) {
+        let (peers, monitors) = {
+            let mut peers = self.peers_mut().await;
+            // Set while holding the lock, so that no peer can sneak in afterwards.
+            self.shut_down.store(true, Ordering::SeqCst);
+
+            (
+                mem::take(&mut *peers),
+                mem::take(&mut *self.monitors.write().await),
+            )
+        };
+        let conns: Vec<_> = peers
+            .values()
+            .map(|peer| (peer.unique_name().clone(), peer.conn().clone()))
+            .chain(
+                monitors
+                    .values()
+                    .map(|monitor| (monitor.unique_name().clone(), monitor.conn().clone())),
+            )
+            .collect();
+        // This stops serving the peers.
+        drop(peers);
+        drop(monitors);
+
+        let mut closing = JoinSet::new();
+        for (unique_name, conn) in conns {
+            // Closing waits for messages being written to go out.
+            closing.spawn(async move {
+                match timeout_at(deadline, conn.close()).await {
+                    Ok(Ok(())) => trace!("Closed connection to peer `{unique_name}`."),
+                    Ok(Err(e)) => debug!(peer = %unique_name, "Failed to close connection: {e}"),
+                    Err(_) => warn!(
+                        peer = %unique_name,
+                        "Dropping connection to peer with messages still being sent."
+                    ),
+                }
+            });
+        }
+        while closing.join_next().await.is_some() {}
+    }
+
+    fn insert(self: &Arc<Self>, peers: &mut BTreeMap<OwnedUniqueName, Peer>, mut peer: Peer) {
+        if self.shut_down.load(Ordering::SeqCst) {
+            debug!("Not adding peer `{}`: shutting down.", peer.unique_name());
+
+            return;
+        }
+
         let unique_name = peer.unique_name().clone();
         match peers.get(&unique_name) {
             Some(peer) => panic!(
@@ -89,19 +219,11 @@ impl Peers {
                     self.clone()
                         .serve_peer(peer_stream, listener, unique_name.clone()),
                 );
-                peers.insert(unique_name.clone(), peer);
+                peers.insert(unique_name, peer);
             }
         }
     }
 
-    pub async fn peers(&self) -> impl Deref<Target = BTreeMap<OwnedUniqueName, Peer>> + '_ {
-        self.peers.read().await
-    }
-
-    pub async fn peers_mut(&self) -> impl DerefMut<Target = BTreeMap<OwnedUniqueName, Peer>> + '_ {
-        self.peers.write().await
-    }
-
     pub async fn name_registry(&self) -> impl Deref<Target = NameRegistry> + '_ {
         self.name_registry.read().await
     }
@@ -115,7 +237,7 @@ impl Peers {
         peer_name: &UniqueName<'_>,
         match_rules: MatchRules,
     ) -> bool {
-        let monitor = {
+        let (monitor_monitoring_fut, unique_name) = {
             let mut peers = self.peers_mut().await;
             let peer = match peers.remove(peer_name.as_str()) {
                 Some(peer) => peer,
@@ -123,17 +245,19 @@ impl Peers {
                     return false;
                 }
             };
+            let monitor = peer.become_monitor(match_rules);
+            let monitor_monitoring_fut = monitor.monitor();
+            let unique_name = monitor.unique_name().clone();
+            // Still holding the peers lock, so that the connection is always counted somewhere.
+            self.monitors
+                .write()
+                .await
+                .insert(unique_name.clone(), monitor);
 
-            peer.become_monitor(match_rules)
+            (monitor_monitoring_fut, unique_name)
         };
 
-        let monitor_monitoring_fut = monitor.monitor();
-        let unique_name = monitor.unique_name().clone();
         let peers = self.clone();
-        self.monitors
-            .write()
-            .await
-            .insert(unique_name.clone(), monitor);
 
         spawn(async move {
             monitor_monitoring_fut.await;
@@ -171,7 +295,11 @@ impl Peers {
                 .unwrap()
                 .build(&name)?;
             if let Err(e) = self.send_msg_to_unique_name(msg, old_owner.clone()).await {
-                warn!("Couldn't notify inexistant peer {old_owner} about loosing name {name}: {e}")
+                warn!(
+                    peer = %old_owner,
+                    %name,
+                    "Couldn't notify inexistant peer about loosing name: {e}"
+                )
             }
         }
         if let Some(new_owner) = new_owner {
@@ -183,7 +311,11 @@ impl Peers {
                 .unwrap()
                 .build(&name)?;
             if let Err(e) = self.send_msg_to_unique_name(msg, new_owner.clone()).await {
-                warn!("Couldn't notify peer {new_owner} about acquiring name {name}: {e}")
+                warn!(
+                    peer = %new_owner,
+                    %name,
+                    "Couldn't notify peer about acquiring name: {e}"
+                )
             }
         }
 
@@ -222,7 +354,12 @@ impl Peers {
                 _ => match msg.header().destination() {
                     Some(dest) => {
                         if let Err(e) = self.send_msg(msg.clone(), dest.clone()).await {
-                            warn!("{}", e);
+                            warn!(
+                                peer = %unique_name,
+                                member = msg.header().member().map(|m| m.as_str()),
+                                "{}",
+                                e
+                            );
                         }
                     }
                     // peer::Stream ensures a valid destination so this isn't exactly needed.
@@ -309,7 +446,12 @@ impl Peers {
                 .await
                 .context("failed to send message")
             {
-                warn!("Error sending message: {}", e);
+                warn!(
+                    peer = %peer.unique_name(),
+                    member = msg.header().member().map(|m| m.as_str()),
+                    "Error sending message: {}",
+                    e
+                );
             }
         }
 
@@ -341,7 +483,12 @@ impl Peers {
                 .await
                 .context("failed to send message")
             {
-                warn!("Error sending message: {}", e);
+                warn!(
+                    peer = %monitor.unique_name(),
+                    member = msg.header().member().map(|m| m.as_str()),
+                    "Error sending message: {}",
+                    e
+                );
             }
         }
     }
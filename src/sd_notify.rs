@@ -0,0 +1,137 @@
+//! Notifying systemd of our state, as `Type=notify` services do.
+//!
+//! See [sd_notify(3)](https://www.freedesktop.org/software/systemd/man/latest/sd_notify.html).
+
+use std::{env, io, os::unix::net::UnixDatagram, path::Path, time::Duration};
+
+use anyhow::Result;
+use tokio::time::{interval, timeout};
+use tracing::{debug, warn};
+use zbus::{fdo::DBusProxy, Connection};
+
+use crate::fdo;
+
+/// How often to update the status when the watchdog isn't enabled.
+const STATUS_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Sends notifications to the service manager.
+#[derive(Debug)]
+pub struct Notifier {
+    socket: UnixDatagram,
+}
+
+impl Notifier {
+    /// A notifier for the socket in `NOTIFY_SOCKET`, if set.
+    pub fn from_env() -> io::Result<Option<Self>> {
+        match env::var_os("NOTIFY_SOCKET") {
+            Some(path) => Self::new(path).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// A notifier for the socket at `path`.
+    ///
+    /// Paths starting with `@` are in the abstract namespace.
+    pub fn new<P>(path: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let socket = UnixDatagram::unbound()?;
+        let path = path.as_ref();
+        match path.as_os_str().as_encoded_bytes() {
+            #[cfg(target_os = "linux")]
+            [b'@', name @ ..] => {
+                use std::os::{linux::net::SocketAddrExt, unix::net::SocketAddr};
+
+                socket.connect_addr(&SocketAddr::from_abstract_name(name)?)?;
+            }
+            _ => socket.connect(path)?,
+        }
+
+        Ok(Self { socket })
+    }
+
+    /// Send `state`, a newline-separated list of variable assignments.
+    pub fn notify(&self, state: &str) -> io::Result<()> {
+        debug!("Notifying the service manager: {state:?}");
+        self.socket.send(state.as_bytes()).map(|_| ())
+    }
+
+    /// Tell the service manager we're ready.
+    pub fn ready(&self) -> io::Result<()> {
+        self.notify("READY=1")
+    }
+
+    /// Tell the service manager we're shutting down.
+    pub fn stopping(&self) -> io::Result<()> {
+        self.notify("STOPPING=1")
+    }
+
+    /// Keep the service manager updated on the bus reachable through `conn`.
+    ///
+    /// The status is updated regularly with the number of peers and names. If `watchdog` is given,
+    /// that happens at that interval and comes with a keep-alive ping. Since it takes a round trip
+    /// through the bus to get the status, the pings stop if the bus stops routing messages.
+    ///
+    /// This only returns on error.
+    pub async fn keep_alive(&self, conn: &Connection, watchdog: Option<Duration>) -> Result<()> {
+        let dbus = DBusProxy::new(conn).await?;
+        let period = watchdog.unwrap_or(STATUS_INTERVAL);
+        let mut ticks = interval(period);
+
+        loop {
+            ticks.tick().await;
+            let all_names = match timeout(period, dbus.list_names()).await {
+                Ok(Ok(names)) => names,
+                Ok(Err(e)) => {
+                    warn!("Failed to get the bus status: {e}");
+
+                    continue;
+                }
+                Err(_) => {
+                    warn!("The bus didn't reply within {period:?}.");
+
+                    continue;
+                }
+            };
+            // Not counting the bus and ourselves.
+            let (peers, names): (Vec<_>, Vec<_>) = all_names
+                .iter()
+                .filter(|name| {
+                    name.as_str() != fdo::BUS_NAME
+                        && Some(name.as_str()) != conn.unique_name().map(|n| n.as_str())
+                })
+                .partition(|name| name.as_str().starts_with(':'));
+
+            let mut state = format!(
+                "STATUS=Connected peers: {}, well-known names: {}",
+                peers.len(),
+                names.len()
+            );
+            if watchdog.is_some() {
+                state.push_str("\nWATCHDOG=1");
+            }
+            if let Err(e) = self.notify(&state) {
+                warn!("Failed to notify the service manager: {e}");
+            }
+        }
+    }
+}
+
+/// The interval at which the service manager expects keep-alive pings from us, if at all.
+///
+/// That's half of `WATCHDOG_USEC`, to leave some room for delays. `WATCHDOG_PID` is honored, so
+/// that the watchdog isn't enabled in child processes.
+pub fn watchdog_interval() -> Option<Duration> {
+    if let Some(pid) = env::var_os("WATCHDOG_PID") {
+        if pid.to_str()?.parse::<u32>().ok()? != std::process::id() {
+            return None;
+        }
+    }
+    let usec = env::var("WATCHDOG_USEC").ok()?.parse::<u64>().ok()?;
+    if usec == 0 {
+        return None;
+    }
+
+    Some(Duration::from_micros(usec) / 2)
+}
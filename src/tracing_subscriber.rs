@@ -1,12 +1,58 @@
+#[cfg(feature = "tracing-subscriber")]
+mod syslog;
+#[cfg(feature = "tracing-subscriber")]
+pub use syslog::*;
+
+/// Whether to log to the system log.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Syslog {
+    /// Only log to standard output.
+    #[default]
+    Off,
+    /// Log to both standard output and the system log.
+    On,
+    /// Only log to the system log.
+    Only,
+}
+
 pub fn init() {
+    init_with_syslog(Syslog::Off);
+}
+
+/// Like [`init`] but also (or only) log to the system log, as configured by `syslog`.
+///
+/// Unless `RUST_LOG` says otherwise, only events of level `INFO` and above end up in the system
+/// log.
+pub fn init_with_syslog(syslog: Syslog) {
+    #[cfg(not(all(feature = "tracing-subscriber", not(feature = "console-subscriber"))))]
+    let _ = syslog;
+
     #[cfg(all(feature = "tracing-subscriber", not(feature = "console-subscriber")))]
     {
-        use tracing_subscriber::{util::SubscriberInitExt, EnvFilter, FmtSubscriber};
+        use tracing_subscriber::{
+            fmt, layer::SubscriberExt, registry, util::SubscriberInitExt, EnvFilter, Layer,
+        };
+
+        let (syslog_layer, syslog_error) = match syslog {
+            Syslog::Off => (None, None),
+            Syslog::On | Syslog::Only => match SyslogLayer::new() {
+                Ok(layer) => {
+                    let filter = EnvFilter::try_from_default_env()
+                        .unwrap_or_else(|_| EnvFilter::new("info"));
+
+                    (Some(layer.with_filter(filter)), None)
+                }
+                Err(e) => (None, Some(e)),
+            },
+        };
+        // Keep logging to standard output if the system log isn't available.
+        let stdout = (syslog != Syslog::Only || syslog_layer.is_none())
+            .then(|| fmt::layer().with_filter(EnvFilter::from_default_env()));
+        registry().with(stdout).with(syslog_layer).init();
 
-        FmtSubscriber::builder()
-            .with_env_filter(EnvFilter::from_default_env())
-            .finish()
-            .init();
+        if let Some(e) = syslog_error {
+            tracing::warn!("Failed to connect to the system log: {e}");
+        }
     }
 
     #[cfg(feature = "console-subscriber")]
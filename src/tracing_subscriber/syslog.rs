@@ -0,0 +1,262 @@
+//! Logging to the system log, the way the reference implementation does it with `<syslog/>`.
+
+use std::{
+    fmt::{self, Write},
+    io,
+    os::unix::net::UnixDatagram,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use tracing::{
+    field::{Field, Visit},
+    Event, Level, Subscriber,
+};
+use tracing_subscriber::{layer::Context, Layer};
+
+/// The socket of the systemd journal.
+pub const JOURNAL_PATH: &str = "/run/systemd/journal/socket";
+/// The socket of the syslog daemon.
+pub const SYSLOG_PATH: &str = "/dev/log";
+
+const IDENTIFIER: &str = "busd";
+/// The `daemon` facility.
+const FACILITY: u8 = 3;
+/// The SD-ID of the structured data element carrying the event fields. 32473 is the private
+/// enterprise number reserved for documentation (RFC 5612), as no number is registered for us.
+const SD_ID: &str = "busd@32473";
+
+/// The format of the messages sent by a [`SyslogLayer`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyslogFormat {
+    /// The native protocol of the systemd journal, with event fields as journal fields.
+    Journal,
+    /// The BSD syslog format (RFC 3164), with event fields appended to the message.
+    Rfc3164,
+    /// The syslog protocol (RFC 5424), with event fields as structured data.
+    Rfc5424,
+}
+
+/// A [`Layer`] sending events to the system log.
+#[derive(Debug)]
+pub struct SyslogLayer {
+    socket: UnixDatagram,
+    path: PathBuf,
+    format: SyslogFormat,
+}
+
+impl SyslogLayer {
+    /// Log to the systemd journal if it's running, or to the syslog daemon otherwise.
+    ///
+    /// Messages to the syslog daemon are in the BSD format, since that's the one all of them
+    /// understand.
+    pub fn new() -> io::Result<Self> {
+        match Self::with_format(JOURNAL_PATH, SyslogFormat::Journal) {
+            Ok(layer) => Ok(layer),
+            Err(_) => Self::with_format(SYSLOG_PATH, SyslogFormat::Rfc3164),
+        }
+    }
+
+    /// Log to the datagram socket at `path`, in `format`.
+    pub fn with_format<P>(path: P, format: SyslogFormat) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(&path)?;
+        // Events are logged from async tasks, which mustn't block on a busy log daemon.
+        socket.set_nonblocking(true)?;
+
+        Ok(Self {
+            socket,
+            path: path.as_ref().to_path_buf(),
+            format,
+        })
+    }
+
+    fn send(&self, message: &[u8]) {
+        // If the log daemon can't keep up, the message is dropped. It may also have been restarted
+        // since, so try again once with a new connection. There's no one to tell if that fails as
+        // well.
+        match self.socket.send(message) {
+            Ok(_) => (),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => (),
+            Err(_) => {
+                if self.socket.connect(&self.path).is_ok() {
+                    let _ = self.socket.send(message);
+                }
+            }
+        }
+    }
+}
+
+impl<S> Layer<S> for SyslogLayer
+where
+    S: Subscriber,
+{
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let metadata = event.metadata();
+        let severity = severity(metadata.level());
+
+        let message = match self.format {
+            SyslogFormat::Journal => journal_message(severity, metadata.target(), &fields),
+            SyslogFormat::Rfc3164 => rfc3164_message(severity, &fields).into_bytes(),
+            SyslogFormat::Rfc5424 => rfc5424_message(severity, &fields).into_bytes(),
+        };
+        self.send(&message);
+    }
+}
+
+/// The syslog severity of `level`.
+fn severity(level: &Level) -> u8 {
+    match *level {
+        Level::ERROR => 3,
+        Level::WARN => 4,
+        Level::INFO => 6,
+        Level::DEBUG | Level::TRACE => 7,
+    }
+}
+
+#[derive(Debug, Default)]
+struct Fields {
+    message: String,
+    others: Vec<(&'static str, String)>,
+}
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            self.others.push((field.name(), value.to_string()));
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{value:?}");
+        } else {
+            self.others.push((field.name(), format!("{value:?}")));
+        }
+    }
+}
+
+fn journal_message(severity: u8, target: &str, fields: &Fields) -> Vec<u8> {
+    let mut message = Vec::new();
+    append_journal_field(&mut message, "PRIORITY", &severity.to_string());
+    append_journal_field(&mut message, "SYSLOG_FACILITY", &FACILITY.to_string());
+    append_journal_field(&mut message, "SYSLOG_IDENTIFIER", IDENTIFIER);
+    append_journal_field(&mut message, "TARGET", target);
+    append_journal_field(&mut message, "MESSAGE", &fields.message);
+    for (name, value) in &fields.others {
+        append_journal_field(&mut message, &journal_field_name(name), value);
+    }
+
+    message
+}
+
+fn append_journal_field(message: &mut Vec<u8>, name: &str, value: &str) {
+    message.extend_from_slice(name.as_bytes());
+    if value.contains('\n') {
+        // Multi-line values need to be prefixed with their length instead.
+        message.push(b'\n');
+        message.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    } else {
+        message.push(b'=');
+    }
+    message.extend_from_slice(value.as_bytes());
+    message.push(b'\n');
+}
+
+/// Journal field names can only contain uppercase letters, digits and underscores, and can't
+/// start with an underscore or a digit.
+fn journal_field_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' => c.to_ascii_uppercase(),
+            _ => '_',
+        })
+        .collect();
+
+    match name.chars().next() {
+        Some('A'..='Z') => name,
+        _ => format!("F{name}"),
+    }
+}
+
+fn rfc3164_message(severity: u8, fields: &Fields) -> String {
+    // The timestamp is left out, so the syslog daemon uses the time it gets the message. It could
+    // only be given in local time anyway.
+    let mut message = format!(
+        "<{}>{IDENTIFIER}[{}]: {}",
+        FACILITY * 8 + severity,
+        std::process::id(),
+        fields.message,
+    );
+    for (name, value) in &fields.others {
+        let _ = write!(message, " {name}={value}");
+    }
+
+    message
+}
+
+fn rfc5424_message(severity: u8, fields: &Fields) -> String {
+    let mut message = format!(
+        "<{}>1 {} - {IDENTIFIER} {} - ",
+        FACILITY * 8 + severity,
+        utc_timestamp(),
+        std::process::id(),
+    );
+    if fields.others.is_empty() {
+        message.push('-');
+    } else {
+        message.push('[');
+        message.push_str(SD_ID);
+        for (name, value) in &fields.others {
+            let value = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace(']', "\\]");
+            let _ = write!(message, " {name}=\"{value}\"");
+        }
+        message.push(']');
+    }
+    if !fields.message.is_empty() {
+        message.push(' ');
+        message.push_str(&fields.message);
+    }
+
+    message
+}
+
+/// The current time in UTC, as an RFC 3339 timestamp with microseconds.
+fn utc_timestamp() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = now.as_secs() as i64;
+    let (days, secs) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+
+    // Converting days since the epoch to a civil date, from
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:06}Z",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+        now.subsec_micros(),
+    )
+}
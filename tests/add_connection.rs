@@ -0,0 +1,61 @@
+use std::os::{fd::OwnedFd, unix::net::UnixStream};
+
+use busd::bus::Bus;
+use nix::unistd::Uid;
+use ntest::timeout;
+use zbus::{connection, fdo::DBusProxy, proxy::CacheProperties, Connection};
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+#[timeout(15000)]
+async fn add_connection() {
+    busd::tracing_subscriber::init();
+
+    // No listener needed.
+    let bus = Bus::without_listener().await.unwrap();
+    assert!(bus.address().is_none());
+
+    let (ours, theirs) = UnixStream::pair().unwrap();
+    theirs.set_nonblocking(true).unwrap();
+    bus.add_connection(tokio::net::UnixStream::from_std(theirs).unwrap(), None)
+        .unwrap();
+    let conn = client(ours).await;
+    assert_eq!(unix_user(&conn).await, Uid::effective().as_raw());
+
+    // Or as a file descriptor.
+    let (ours, theirs) = UnixStream::pair().unwrap();
+    bus.add_connection_fd(OwnedFd::from(theirs), None).unwrap();
+    let conn2 = client(ours).await;
+    assert_eq!(unix_user(&conn2).await, Uid::effective().as_raw());
+    assert_ne!(conn.unique_name(), conn2.unique_name());
+
+    // In-process connections can be mixed in.
+    let conn3 = bus.connect_in_process().await.unwrap();
+    assert_eq!(unix_user(&conn3).await, Uid::effective().as_raw());
+
+    bus.cleanup().await.unwrap();
+}
+
+async fn client(stream: UnixStream) -> Connection {
+    stream.set_nonblocking(true).unwrap();
+    let stream = tokio::net::UnixStream::from_std(stream).unwrap();
+    let conn = connection::Builder::unix_stream(stream)
+        .build()
+        .await
+        .unwrap();
+    assert!(conn.unique_name().is_some());
+
+    conn
+}
+
+async fn unix_user(conn: &Connection) -> u32 {
+    let dbus_proxy = DBusProxy::builder(conn)
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await
+        .unwrap();
+
+    dbus_proxy
+        .get_connection_unix_user(conn.unique_name().unwrap().clone().into())
+        .await
+        .unwrap()
+}
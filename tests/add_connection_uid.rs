@@ -0,0 +1,73 @@
+use std::{env::temp_dir, os::unix::net::UnixStream};
+
+use busd::{
+    bus::{self, Bus},
+    config::{Access, Config, ConnectOperation, Limits, Operation, Policy},
+};
+use ntest::timeout;
+use rand::{
+    distr::{Alphanumeric, SampleString},
+    rng,
+};
+use zbus::{connection, fdo::DBusProxy, proxy::CacheProperties, Connection};
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+#[timeout(15000)]
+async fn add_connection_uid() {
+    busd::tracing_subscriber::init();
+
+    let s = Alphanumeric.sample_string(&mut rng(), 10);
+    let address = format!("unix:path={}", temp_dir().join(s).display());
+    let allow = |user: &str| {
+        (
+            Access::Allow,
+            Operation::Connect(ConnectOperation {
+                user: Some(user.to_string()),
+                group: None,
+            }),
+        )
+    };
+    let config = Config {
+        listen: Some(bus::parse_address(&address).unwrap()),
+        policies: vec![Policy::DefaultContext(vec![allow("4242")])],
+        limits: Limits {
+            max_connections_per_user: 1,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let bus = Bus::for_config(config).await.unwrap();
+
+    // The connect policy and the limits apply to the user the connection is added as, which is
+    // also the user it's reported as.
+    let conn = add(&bus, 4242).await.unwrap();
+    assert_eq!(unix_user(&conn).await, 4242);
+    add(&bus, 4242).await.unwrap_err();
+    add(&bus, 4343).await.unwrap_err();
+
+    bus.cleanup().await.unwrap();
+}
+
+async fn add(bus: &Bus, uid: u32) -> zbus::Result<Connection> {
+    let (ours, theirs) = UnixStream::pair().unwrap();
+    theirs.set_nonblocking(true).unwrap();
+    bus.add_connection(tokio::net::UnixStream::from_std(theirs).unwrap(), Some(uid))
+        .unwrap();
+    ours.set_nonblocking(true).unwrap();
+    let stream = tokio::net::UnixStream::from_std(ours).unwrap();
+
+    connection::Builder::unix_stream(stream).build().await
+}
+
+async fn unix_user(conn: &Connection) -> u32 {
+    let dbus_proxy = DBusProxy::builder(conn)
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await
+        .unwrap();
+
+    dbus_proxy
+        .get_connection_unix_user(conn.unique_name().unwrap().clone().into())
+        .await
+        .unwrap()
+}
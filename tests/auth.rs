@@ -0,0 +1,238 @@
+mod common;
+
+use std::{env::temp_dir, fs::Permissions, os::unix::fs::PermissionsExt, path::Path};
+
+use busd::{
+    auth::{handshake, Authenticated, Mechanism},
+    bus::Bus,
+    config::Config,
+};
+use nix::unistd::Uid;
+use ntest::timeout;
+use rand::{
+    distr::{Alphanumeric, SampleString},
+    rng,
+};
+use tokio::{
+    join,
+    net::{TcpListener, TcpStream},
+};
+use zbus::{
+    connection::{self, socket::BoxedSplit},
+    AuthMechanism, Guid, OwnedGuid,
+};
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+#[timeout(15000)]
+async fn auth() {
+    busd::tracing_subscriber::init();
+
+    // By default, anonymous peers are not welcome.
+    let config = auth_config(&common::unix_address(), vec![], false);
+    let mut bus = Bus::for_config(config).await.unwrap();
+    assert_eq!(
+        bus.auth_mechanisms(),
+        &[Mechanism::External, Mechanism::CookieSha1]
+    );
+    common::serve(&mut bus, |address| async move {
+        assert!(connect(&address, AuthMechanism::External).await);
+        assert!(!connect(&address, AuthMechanism::Anonymous).await);
+    })
+    .await;
+    bus.cleanup().await.unwrap();
+
+    // Unless anonymous peers are allowed.
+    let config = auth_config(&common::unix_address(), vec![], true);
+    let mut bus = Bus::for_config(config).await.unwrap();
+    assert_eq!(
+        bus.auth_mechanisms(),
+        &[
+            Mechanism::External,
+            Mechanism::CookieSha1,
+            Mechanism::Anonymous
+        ]
+    );
+    common::serve(&mut bus, |address| async move {
+        assert!(connect(&address, AuthMechanism::External).await);
+        assert!(connect(&address, AuthMechanism::Anonymous).await);
+    })
+    .await;
+    bus.cleanup().await.unwrap();
+
+    // Only the configured mechanisms are offered.
+    let config = auth_config(&common::unix_address(), vec![Mechanism::Anonymous], true);
+    let mut bus = Bus::for_config(config).await.unwrap();
+    common::serve(&mut bus, |address| async move {
+        assert!(!connect(&address, AuthMechanism::External).await);
+        assert!(connect(&address, AuthMechanism::Anonymous).await);
+    })
+    .await;
+    bus.cleanup().await.unwrap();
+
+    // EXTERNAL doesn't work over TCP.
+    let tcp = "tcp:host=127.0.0.1,port=0";
+    let config = auth_config(tcp, vec![Mechanism::External], false);
+    assert!(Bus::for_config(config).await.is_err());
+
+    // Nothing to offer on TCP without anonymous peers allowed.
+    let config = auth_config(tcp, vec![Mechanism::Anonymous], false);
+    assert!(Bus::for_config(config).await.is_err());
+
+    cookie_sha1().await;
+}
+
+async fn cookie_sha1() {
+    // Only DBUS_COOKIE_SHA1 is offered on TCP.
+    let config = auth_config("tcp:host=127.0.0.1,port=0", vec![], false);
+    let mut bus = Bus::for_config(config).await.unwrap();
+    assert_eq!(bus.auth_mechanisms(), &[Mechanism::CookieSha1]);
+    common::serve(&mut bus, |address| async move {
+        // Anonymous TCP clients are not welcome anymore.
+        assert!(!connect(&address, AuthMechanism::Anonymous).await);
+    })
+    .await;
+    bus.cleanup().await.unwrap();
+
+    // The handshake is run on its own to keep the keyring out of the actual home directory.
+    let keyring = temp_dir().join(Alphanumeric.sample_string(&mut rng(), 10));
+    let authenticated = cookie_handshake(&keyring, true).await.unwrap();
+    assert_eq!(authenticated.mechanism, Mechanism::CookieSha1);
+    assert_eq!(authenticated.uid, Some(Uid::effective().as_raw()));
+
+    // The keyring was created private.
+    let mode = std::fs::metadata(&keyring).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o700);
+
+    // Knowing the cookie is what counts.
+    assert!(cookie_handshake(&keyring, false).await.is_err());
+
+    // Cookies can't be trusted if others could have read them.
+    std::fs::set_permissions(&keyring, Permissions::from_mode(0o755)).unwrap();
+    assert!(cookie_handshake(&keyring, true).await.is_err());
+
+    std::fs::remove_dir_all(keyring).unwrap();
+}
+
+/// Run [`handshake`] with the keyring in `keyring` against a [`cookie_client`] over TCP.
+async fn cookie_handshake(keyring: &Path, correct: bool) -> anyhow::Result<Authenticated> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let address = listener.local_addr()?;
+    let server = async {
+        let (stream, _) = listener.accept().await?;
+        let mut socket: BoxedSplit = stream.into();
+        let guid: OwnedGuid = Guid::generate().into();
+
+        handshake(
+            &mut socket,
+            &guid,
+            &[Mechanism::CookieSha1],
+            None,
+            Some(keyring),
+        )
+        .await
+    };
+    let client = async {
+        let mut stream = TcpStream::connect(address).await?;
+
+        cookie_client::authenticate(&mut stream, keyring, correct).await
+    };
+    let (server, client) = join!(server, client);
+    client?;
+
+    server
+}
+
+/// A client for the `DBUS_COOKIE_SHA1` mechanism, which zbus doesn't implement.
+mod cookie_client {
+    use std::path::Path;
+
+    use anyhow::{anyhow, bail, Result};
+    use sha1::{Digest, Sha1};
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::TcpStream,
+    };
+
+    /// Authenticate as the current user on `stream`, with a wrong cookie unless `correct`.
+    pub async fn authenticate(stream: &mut TcpStream, keyring: &Path, correct: bool) -> Result<()> {
+        let uid = nix::unistd::Uid::effective().to_string();
+        let data = exchange(
+            stream,
+            &format!("\0AUTH DBUS_COOKIE_SHA1 {}", encode(uid.as_bytes())),
+        )
+        .await?;
+        let challenge = data
+            .strip_prefix("DATA ")
+            .ok_or_else(|| anyhow!("Unexpected reply `{data}`"))?;
+        let challenge = String::from_utf8(decode(challenge))?;
+        let mut fields = challenge.split(' ');
+        let (context, id, server_challenge) = match (fields.next(), fields.next(), fields.next()) {
+            (Some(context), Some(id), Some(challenge)) => (context, id, challenge),
+            _ => bail!("Invalid challenge `{challenge}`"),
+        };
+
+        let keyring = std::fs::read_to_string(keyring.join(context))?;
+        let cookie = keyring
+            .lines()
+            .find_map(|line| {
+                let mut fields = line.split(' ');
+                (fields.next() == Some(id)).then(|| fields.nth(1).unwrap().to_string())
+            })
+            .ok_or_else(|| anyhow!("No cookie {id}"))?;
+        let cookie = if correct { cookie } else { "0".repeat(48) };
+
+        let client_challenge = "0123456789abcdef";
+        let digest = encode(&Sha1::digest(format!(
+            "{server_challenge}:{client_challenge}:{cookie}"
+        )));
+        let response = format!("{client_challenge} {digest}");
+        let reply = exchange(stream, &format!("DATA {}", encode(response.as_bytes()))).await?;
+        if !reply.starts_with("OK ") {
+            bail!("Unexpected reply `{reply}`");
+        }
+        stream.write_all(b"BEGIN\r\n").await?;
+
+        Ok(())
+    }
+
+    async fn exchange(stream: &mut TcpStream, line: &str) -> Result<String> {
+        stream.write_all(format!("{line}\r\n").as_bytes()).await?;
+
+        let mut reply = String::new();
+        BufReader::new(stream).read_line(&mut reply).await?;
+        if reply.is_empty() {
+            bail!("Connection closed");
+        }
+
+        Ok(reply.trim_end().to_string())
+    }
+
+    fn encode(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    fn decode(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+}
+
+/// The configuration of a bus listening on `address`, offering the `auth` mechanisms.
+fn auth_config(address: &str, auth: Vec<Mechanism>, allow_anonymous: bool) -> Config {
+    Config {
+        auth,
+        allow_anonymous,
+        ..common::config_for(address)
+    }
+}
+
+async fn connect(address: &str, mechanism: AuthMechanism) -> bool {
+    connection::Builder::address(address)
+        .unwrap()
+        .auth_mechanism(mechanism)
+        .build()
+        .await
+        .is_ok()
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use std::time::Duration;
+
+use busd::{
+    bus::Bus,
+    config::{Config, Limits},
+};
+use ntest::timeout;
+use tokio::{io::AsyncReadExt, net::UnixStream};
+use zbus::connection;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+#[timeout(15000)]
+async fn auth_timeout() {
+    busd::tracing_subscriber::init();
+
+    let path = common::socket_path();
+    let config = Config {
+        limits: Limits {
+            auth_timeout: Duration::from_millis(500),
+            max_incomplete_connections: 2,
+            ..Default::default()
+        },
+        ..common::config_for(&format!("unix:path={}", path.display()))
+    };
+    let mut bus = Bus::for_config(config).await.unwrap();
+    common::serve(&mut bus, |address| async move {
+        // Two clients that never authenticate use up all the slots.
+        let mut stalled1 = UnixStream::connect(&path).await.unwrap();
+        let mut stalled2 = UnixStream::connect(&path).await.unwrap();
+
+        // So the next one is dropped right away.
+        let mut dropped = UnixStream::connect(&path).await.unwrap();
+        assert_eq!(read_to_end(&mut dropped).await, 0);
+
+        // The stalled ones are dropped once their time is up.
+        assert_eq!(read_to_end(&mut stalled1).await, 0);
+        assert_eq!(read_to_end(&mut stalled2).await, 0);
+
+        // Which makes room for clients that do authenticate.
+        let _conn = connection::Builder::address(&*address)
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+    })
+    .await;
+    assert_eq!(bus.incomplete_connections(), 0);
+    assert_eq!(bus.dropped_connections(), 3);
+
+    // Connections added directly are refused with an error instead.
+    let (_stalled1, theirs1) = UnixStream::pair().unwrap();
+    bus.add_connection(theirs1, None).unwrap();
+    let (_stalled2, theirs2) = UnixStream::pair().unwrap();
+    bus.add_connection(theirs2, None).unwrap();
+    let (_refused, theirs3) = UnixStream::pair().unwrap();
+    bus.add_connection(theirs3, None).unwrap_err();
+    assert_eq!(bus.incomplete_connections(), 2);
+    assert_eq!(bus.dropped_connections(), 4);
+    bus.cleanup().await.unwrap();
+}
+
+async fn read_to_end(stream: &mut UnixStream) -> usize {
+    let mut buf = vec![];
+
+    stream.read_to_end(&mut buf).await.unwrap()
+}
@@ -0,0 +1,97 @@
+use std::{
+    env::temp_dir,
+    fs,
+    io::{BufRead, BufReader, Read},
+    process::{Command, Stdio},
+};
+
+use nix::{
+    sys::signal::{kill, Signal},
+    unistd::Pid,
+};
+use ntest::timeout;
+use rand::{
+    distr::{Alphanumeric, SampleString},
+    rng,
+};
+
+#[test]
+#[timeout(15000)]
+fn cli() {
+    busd::tracing_subscriber::init();
+
+    // The introspection data of the driver.
+    let output = Command::new(env!("CARGO_BIN_EXE_busd"))
+        .arg("--introspect")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let xml = String::from_utf8(output.stdout).unwrap();
+    assert!(xml.contains(r#"<interface name="org.freedesktop.DBus">"#));
+    assert!(xml.contains(r#"<method name="Hello">"#));
+
+    // `--address` overrides `<listen>` in the file given with `--config-file`. Readiness, the
+    // address and the PID can all go to standard output.
+    let dir = temp_dir().join(Alphanumeric.sample_string(&mut rng(), 10));
+    fs::create_dir(&dir).unwrap();
+    let config_path = dir.join("bus.conf");
+    fs::write(
+        &config_path,
+        format!(
+            "<busconfig><listen>unix:path={}</listen></busconfig>",
+            dir.join("ignored").display()
+        ),
+    )
+    .unwrap();
+    let socket_path = dir.join("bus");
+    let mut busd = Command::new(env!("CARGO_BIN_EXE_busd"))
+        .arg(format!("--config-file={}", config_path.display()))
+        .arg(format!("--address=unix:path={}", socket_path.display()))
+        .args([
+            "--nofork",
+            "--ready-fd=1",
+            "--print-address",
+            "--print-pid=1",
+        ])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut lines = BufReader::new(busd.stdout.take().unwrap()).lines();
+    assert_eq!(lines.next().unwrap().unwrap(), "READY=1");
+    let address = lines.next().unwrap().unwrap();
+    assert!(address.starts_with(&format!("unix:path={},guid=", socket_path.display())));
+    let pid = lines.next().unwrap().unwrap();
+    assert_eq!(pid, busd.id().to_string());
+    assert!(!dir.join("ignored").exists());
+
+    kill(Pid::from_raw(busd.id() as i32), Signal::SIGTERM).unwrap();
+    assert!(busd.wait().unwrap().success());
+
+    // Other file descriptors are closed once written to, even if given more than once.
+    let mut busd = Command::new("sh")
+        .arg("-c")
+        .arg(format!(
+            "exec '{}' --config-file='{}' --address=unix:path='{}' --nofork --ready-fd=3 \
+             --print-address=3 3>&1 1>/dev/null",
+            env!("CARGO_BIN_EXE_busd"),
+            config_path.display(),
+            socket_path.display(),
+        ))
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut output = String::new();
+    busd.stdout
+        .take()
+        .unwrap()
+        .read_to_string(&mut output)
+        .unwrap();
+    let (ready, address) = output.trim_end().split_once('\n').unwrap();
+    assert_eq!(ready, "READY=1");
+    assert!(address.starts_with(&format!("unix:path={},guid=", socket_path.display())));
+
+    kill(Pid::from_raw(busd.id() as i32), Signal::SIGTERM).unwrap();
+    assert!(busd.wait().unwrap().success());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
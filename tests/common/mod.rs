@@ -0,0 +1,56 @@
+//! Helpers shared by the integration tests.
+//!
+//! Not every test uses all of them.
+#![allow(dead_code)]
+
+use std::{env::temp_dir, future::Future, path::PathBuf};
+
+use busd::{
+    bus::{self, Bus},
+    config::Config,
+};
+use rand::{
+    distr::{Alphanumeric, SampleString},
+    rng,
+};
+use tokio::select;
+
+/// The configuration of a bus listening on `address`, which zbus clients can connect to whatever
+/// the transport.
+///
+/// zbus clients can only authenticate anonymously over TCP, so anonymous peers are allowed.
+pub fn config_for(address: &str) -> Config {
+    Config {
+        listen: Some(bus::parse_address(address).unwrap()),
+        allow_anonymous: true,
+        ..Default::default()
+    }
+}
+
+/// A new path for a UNIX socket, in the temporary directory.
+pub fn socket_path() -> PathBuf {
+    let s = Alphanumeric.sample_string(&mut rng(), 10);
+
+    temp_dir().join(s)
+}
+
+/// The address of a UNIX socket at a new [`socket_path`].
+pub fn unix_address() -> String {
+    format!("unix:path={}", socket_path().display())
+}
+
+/// Accept connections on `bus` until `client`, given the address of the bus, is done.
+///
+/// The bus is left as is afterwards, so it can still be inspected before it's cleaned up.
+pub async fn serve<F, Fut>(bus: &mut Bus, client: F) -> Fut::Output
+where
+    F: FnOnce(String) -> Fut,
+    Fut: Future,
+{
+    let address = bus.address().unwrap().to_string();
+
+    select! {
+        res = bus.run() => panic!("Bus exited unexpectedly: {res:?}"),
+        output = client(address) => output,
+    }
+}
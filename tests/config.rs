@@ -1,10 +1,13 @@
-use std::{path::PathBuf, str::FromStr};
+use std::{path::PathBuf, str::FromStr, time::Duration};
 
-use busd::config::{
-    Access, BusType, Config, ConnectOperation, MessageType, Name, NameOwnership, Operation, Policy,
-    ReceiveOperation, SendOperation,
+use busd::{
+    auth::Mechanism,
+    config::{
+        Access, BusType, Config, ConnectOperation, Limits, MessageType, Name, NameOwnership,
+        Operation, Policy, ReceiveOperation, SendOperation, Sources,
+    },
 };
-use zbus::{Address, AuthMechanism};
+use zbus::Address;
 
 #[test]
 fn config_read_file_with_includes_ok() {
@@ -14,7 +17,7 @@ fn config_read_file_with_includes_ok() {
     assert_eq!(
         got,
         Config {
-            auth: Some(AuthMechanism::External),
+            auth: vec![Mechanism::Anonymous, Mechanism::External],
             listen: Some(Address::from_str("unix:path=/tmp/a").expect("should parse address")),
             policies: vec![
                 Policy::DefaultContext(vec![
@@ -51,6 +54,25 @@ fn config_read_file_with_includes_ok() {
     );
 }
 
+#[test]
+fn config_read_file_with_sources_ok() {
+    let (_, sources) = Config::read_file_with_sources("./tests/data/valid.conf")
+        .expect("should read and parse XML input");
+    let data_dir = PathBuf::from("./tests/data").canonicalize().unwrap();
+
+    assert_eq!(
+        sources,
+        Sources {
+            files: vec![
+                PathBuf::from("./tests/data/valid.conf"),
+                data_dir.join("valid_included.conf"),
+                data_dir.join("includedir/a.conf"),
+            ],
+            dirs: vec![data_dir.join("includedir")],
+        }
+    );
+}
+
 #[test]
 fn config_read_file_example_session_disable_stats_conf_ok() {
     let got = Config::read_file("./tests/data/example-session-disable-stats.conf")
@@ -125,6 +147,24 @@ fn config_read_file_session_conf_ok() {
                 Address::from_str("unix:path=/run/user/1000/bus").expect("should parse address")
             ),
             keep_umask: true,
+            limits: Limits {
+                max_incoming_bytes: 1000000000,
+                max_incoming_unix_fds: 250000000,
+                max_outgoing_bytes: 1000000000,
+                max_outgoing_unix_fds: 250000000,
+                max_message_size: 1000000000,
+                service_start_timeout: Duration::from_secs(120),
+                auth_timeout: Duration::from_secs(240),
+                pending_fd_timeout: Duration::from_secs(150),
+                max_completed_connections: 100000,
+                max_incomplete_connections: 10000,
+                max_connections_per_user: 100000,
+                max_pending_service_starts: 10000,
+                max_names_per_connection: 50000,
+                max_match_rules_per_connection: 50000,
+                max_replies_per_connection: 50000,
+                ..Default::default()
+            },
             policies: vec![Policy::DefaultContext(vec![
                 (
                     Access::Allow,
@@ -156,7 +196,7 @@ fn config_read_file_session_conf_ok() {
 #[test]
 fn config_read_file_system_conf_ok() {
     let want = Config {
-        auth: Some(AuthMechanism::External),
+        auth: vec![Mechanism::External],
         fork: true,
         listen: Some(
             Address::from_str("unix:path=/var/run/dbus/system_bus_socket")
@@ -0,0 +1,101 @@
+#![cfg(target_os = "linux")]
+
+use std::{
+    env::temp_dir,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use busd::{bus::Bus, config::Config};
+use ntest::timeout;
+use rand::{
+    distr::{Alphanumeric, SampleString},
+    rng,
+};
+use tokio::time::sleep;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+#[timeout(15000)]
+async fn config_watch() {
+    busd::tracing_subscriber::init();
+
+    let dir = temp_dir().join(Alphanumeric.sample_string(&mut rng(), 10));
+    fs::create_dir_all(dir.join("system.d")).unwrap();
+    let config_path = dir.join("bus.conf");
+    let main_config = |includedirs: &str| {
+        format!(
+            r#"<listen>unix:path={}</listen>
+            <include>included.conf</include>
+            <includedir>system.d</includedir>
+            {includedirs}"#,
+            dir.join("socket").display(),
+        )
+    };
+    write_config(&config_path, &main_config(""));
+    write_config(&dir.join("included.conf"), "<servicedir>/a</servicedir>");
+
+    let mut bus = Bus::for_config(Config::read_file(&config_path).unwrap())
+        .await
+        .unwrap();
+    bus.set_config_file(&config_path);
+    bus.watch_config_file().unwrap();
+
+    // New snippets in an `<includedir>` apply on their own. Other files there don't matter.
+    write_config(&dir.join("system.d/b.conf"), "<servicedir>/b</servicedir>");
+    fs::write(dir.join("system.d/b.conf.bak"), "garbage").unwrap();
+    wait_for_servicedirs(&bus, &["/a", "/b"]).await;
+
+    // Included files replaced by renaming over them too.
+    write_config(&dir.join("included.tmp"), "<servicedir>/c</servicedir>");
+    fs::rename(dir.join("included.tmp"), dir.join("included.conf")).unwrap();
+    wait_for_servicedirs(&bus, &["/c", "/b"]).await;
+
+    // Broken files are ignored until fixed.
+    fs::write(dir.join("system.d/d.conf"), "<busconfig><oops>").unwrap();
+    sleep(Duration::from_secs(1)).await;
+    wait_for_servicedirs(&bus, &["/c", "/b"]).await;
+    write_config(&dir.join("system.d/d.conf"), "<servicedir>/d</servicedir>");
+    wait_for_servicedirs(&bus, &["/c", "/b", "/d"]).await;
+
+    // Directories added to the configuration are watched as well.
+    fs::create_dir(dir.join("extra.d")).unwrap();
+    write_config(
+        &config_path,
+        &main_config("<includedir>extra.d</includedir>"),
+    );
+    write_config(&dir.join("extra.d/e.conf"), "<servicedir>/e</servicedir>");
+    wait_for_servicedirs(&bus, &["/c", "/b", "/d", "/e"]).await;
+    fs::remove_file(dir.join("extra.d/e.conf")).unwrap();
+    wait_for_servicedirs(&bus, &["/c", "/b", "/d"]).await;
+
+    bus.cleanup().await.unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+fn write_config(path: &Path, elements: &str) {
+    let config = format!(
+        r#"<!DOCTYPE busconfig PUBLIC "-//freedesktop//DTD D-Bus Bus Configuration 1.0//EN"
+    "http://www.freedesktop.org/standards/dbus/1.0/busconfig.dtd">
+<busconfig>
+    {elements}
+</busconfig>
+"#
+    );
+    fs::write(path, config).unwrap();
+}
+
+async fn wait_for_servicedirs(bus: &Bus, expected: &[&str]) {
+    let mut expected: Vec<_> = expected.iter().map(PathBuf::from).collect();
+    expected.sort();
+    // Files in `<includedir>` are included in no particular order.
+    loop {
+        let mut servicedirs = bus.config().servicedirs.clone();
+        servicedirs.sort();
+        if servicedirs == expected {
+            break;
+        }
+
+        sleep(Duration::from_millis(50)).await;
+    }
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use busd::{
+    bus::{self, Bus},
+    config::{Access, Config, ConnectOperation, Operation, Policy},
+};
+use nix::unistd::Uid;
+use ntest::timeout;
+use zbus::connection;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+#[timeout(15000)]
+async fn connect_policy() {
+    busd::tracing_subscriber::init();
+
+    // The bus owner can connect by default.
+    assert!(can_connect(vec![]).await);
+
+    // Unless they are explicitly denied.
+    let us = Uid::effective().to_string();
+    let deny_us = (Access::Deny, connect_rule(Some(&us), None));
+    assert!(!can_connect(vec![Policy::DefaultContext(vec![deny_us.clone()])]).await);
+
+    // The last matching rule wins.
+    let allow_all = (Access::Allow, connect_rule(Some("*"), None));
+    assert!(
+        can_connect(vec![Policy::DefaultContext(vec![
+            deny_us.clone(),
+            allow_all.clone()
+        ])])
+        .await
+    );
+    assert!(
+        !can_connect(vec![Policy::DefaultContext(vec![
+            allow_all.clone(),
+            deny_us.clone()
+        ])])
+        .await
+    );
+
+    // Mandatory rules come last, whatever the order of the policies.
+    assert!(
+        can_connect(vec![
+            Policy::MandatoryContext(vec![allow_all.clone()]),
+            Policy::DefaultContext(vec![deny_us.clone()]),
+        ])
+        .await
+    );
+
+    // Groups work too.
+    let deny_all_groups = (Access::Deny, connect_rule(None, Some("*")));
+    assert!(!can_connect(vec![Policy::DefaultContext(vec![deny_all_groups])]).await);
+
+    // Per-user policies don't apply to connecting.
+    assert!(can_connect(vec![Policy::User(vec![deny_us], us)]).await);
+}
+
+fn connect_rule(user: Option<&str>, group: Option<&str>) -> Operation {
+    Operation::Connect(ConnectOperation {
+        user: user.map(ToString::to_string),
+        group: group.map(ToString::to_string),
+    })
+}
+
+async fn can_connect(policies: Vec<Policy>) -> bool {
+    let config = Config {
+        listen: Some(bus::parse_address(&common::unix_address()).unwrap()),
+        policies,
+        ..Default::default()
+    };
+    let mut bus = Bus::for_config(config).await.unwrap();
+    let connected = common::serve(&mut bus, |address| async move {
+        connection::Builder::address(&*address)
+            .unwrap()
+            .build()
+            .await
+            .is_ok()
+    })
+    .await;
+    bus.cleanup().await.unwrap();
+
+    connected
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use std::{collections::BTreeMap, time::Duration};
+
+use busd::{
+    bus::{self, Bus},
+    config::{Config, Limits},
+    peers::{ConnectionCounts, Peers},
+};
+use nix::unistd::Uid;
+use ntest::timeout;
+use zbus::{connection, Connection};
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+#[timeout(15000)]
+async fn connection_limits() {
+    busd::tracing_subscriber::init();
+
+    let config = Config {
+        listen: Some(bus::parse_address(&common::unix_address()).unwrap()),
+        limits: Limits {
+            max_completed_connections: 4,
+            max_connections_per_user: 2,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut bus = Bus::for_config(config).await.unwrap();
+    let peers = bus.peers().clone();
+    // In-process connections aren't limited but they count.
+    let in_process1 = bus.connect_in_process().await.unwrap();
+    let in_process2 = bus.connect_in_process().await.unwrap();
+    let _in_process3 = bus.connect_in_process().await.unwrap();
+    let uid = Uid::current().as_raw();
+    // The connections are kept until the counts are checked below.
+    let _conns = common::serve(&mut bus, |address| async move {
+        // Up to four connections in total.
+        let conn1 = connect(&address).await.unwrap();
+        wait_for_counts(&peers, 4, &[(uid, 1)]).await;
+        connect(&address).await.unwrap_err();
+
+        // Up to two connections per user.
+        drop(in_process1);
+        drop(in_process2);
+        wait_for_counts(&peers, 2, &[(uid, 1)]).await;
+        let conn2 = connect(&address).await.unwrap();
+        wait_for_counts(&peers, 3, &[(uid, 2)]).await;
+        connect(&address).await.unwrap_err();
+
+        (conn1, conn2)
+    })
+    .await;
+    assert_eq!(
+        bus.connection_counts().await,
+        ConnectionCounts {
+            total: 3,
+            per_user: BTreeMap::from([(uid, 2)]),
+        }
+    );
+    bus.cleanup().await.unwrap();
+}
+
+async fn connect(address: &str) -> zbus::Result<Connection> {
+    connection::Builder::address(address)?.build().await
+}
+
+async fn wait_for_counts(peers: &Peers, total: usize, per_user: &[(u32, usize)]) {
+    let expected = ConnectionCounts {
+        total,
+        per_user: per_user.iter().copied().collect::<BTreeMap<_, _>>(),
+    };
+    while peers.connection_counts().await != expected {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
@@ -1,3 +1,5 @@
+mod common;
+
 use std::env::temp_dir;
 
 use anyhow::ensure;
@@ -31,12 +33,13 @@ async fn name_ownership_changes() {
     name_ownership_changes_(&address).await;
 
     // TCP socket
-    let address = "tcp:host=127.0.0.1,port=4242".to_string();
+    let address = "tcp:host=127.0.0.1,port=0".to_string();
     name_ownership_changes_(&address).await;
 }
 
 async fn name_ownership_changes_(address: &str) {
-    let mut bus = Bus::for_address(Some(address)).await.unwrap();
+    let mut bus = Bus::for_config(common::config_for(address)).await.unwrap();
+    let address = bus.address().unwrap().to_string();
     let (tx, rx) = tokio::sync::oneshot::channel();
 
     let handle = tokio::spawn(async move {
@@ -51,7 +54,7 @@ async fn name_ownership_changes_(address: &str) {
         bus
     });
 
-    let ret = name_ownership_changes_client(address, tx).await;
+    let ret = name_ownership_changes_client(&address, tx).await;
     let bus = handle.await.unwrap();
     bus.cleanup().await.unwrap();
     ret.unwrap();
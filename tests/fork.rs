@@ -0,0 +1,122 @@
+use std::{
+    env::temp_dir,
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+    time::Duration,
+};
+
+use nix::{
+    sys::signal::{kill, Signal},
+    unistd::{getsid, Pid},
+};
+use ntest::timeout;
+use rand::{
+    distr::{Alphanumeric, SampleString},
+    rng,
+};
+use zbus::{connection, fdo::DBusProxy, names::BusName};
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+#[timeout(15000)]
+async fn fork() {
+    busd::tracing_subscriber::init();
+
+    let dir = temp_dir().join(Alphanumeric.sample_string(&mut rng(), 10));
+    fs::create_dir(&dir).unwrap();
+    let socket_path = dir.join("bus");
+    let config_path = write_config(&dir, &socket_path, true);
+
+    // The address is only printed once the daemon is listening.
+    let output = busd(&["--config", config_path.to_str().unwrap(), "--print-address"]);
+    assert!(output.status.success(), "{output:?}");
+    let address = String::from_utf8(output.stdout).unwrap();
+    let address = address.trim_end();
+    assert!(address.starts_with(&format!("unix:path={}", socket_path.display())));
+
+    let pid = bus_pid(address).await;
+
+    // The daemon is in a session of its own, which it doesn't lead, and in `/`.
+    let sid = getsid(Some(pid)).unwrap();
+    assert_ne!(sid, getsid(None).unwrap());
+    assert_ne!(sid, pid);
+    assert_eq!(
+        fs::read_link(format!("/proc/{pid}/cwd")).unwrap(),
+        Path::new("/")
+    );
+
+    kill(pid, Signal::SIGINT).unwrap();
+    while socket_path.exists() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    // The daemon doesn't keep the file descriptors it's announced on open.
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(format!(
+            "'{}' --config='{}' --print-address=3 3>&1 1>/dev/null",
+            env!("CARGO_BIN_EXE_busd"),
+            config_path.display(),
+        ))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let address = String::from_utf8(output.stdout).unwrap();
+    let pid = bus_pid(address.trim_end()).await;
+    kill(pid, Signal::SIGINT).unwrap();
+    while socket_path.exists() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    // Failures of the daemon to start are reported by the original process.
+    let config_path = write_config(&dir, &dir.join("no-such-dir/bus"), false);
+    let output = busd(&["--config", config_path.to_str().unwrap(), "--fork"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Daemon failed to start"), "{stderr}");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+fn write_config(dir: &Path, socket_path: &Path, fork: bool) -> PathBuf {
+    let config_path = dir.join("bus.conf");
+    let fork = if fork { "<fork/>" } else { "" };
+    fs::write(
+        &config_path,
+        format!(
+            r#"<!DOCTYPE busconfig PUBLIC "-//freedesktop//DTD D-Bus Bus Configuration 1.0//EN"
+            "http://www.freedesktop.org/standards/dbus/1.0/busconfig.dtd">
+            <busconfig>
+                <listen>unix:path={}</listen>
+                {fork}
+            </busconfig>"#,
+            socket_path.display()
+        ),
+    )
+    .unwrap();
+
+    config_path
+}
+
+fn busd(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_busd"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+async fn bus_pid(address: &str) -> Pid {
+    let conn = connection::Builder::address(address)
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+    let pid = DBusProxy::new(&conn)
+        .await
+        .unwrap()
+        .get_connection_unix_process_id(BusName::try_from("org.freedesktop.DBus").unwrap())
+        .await
+        .unwrap();
+
+    Pid::from_raw(pid as i32)
+}
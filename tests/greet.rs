@@ -1,3 +1,5 @@
+mod common;
+
 use std::{env::temp_dir, time::Duration};
 
 use anyhow::anyhow;
@@ -34,12 +36,15 @@ async fn greet() {
     greet_(&address).await;
 
     // TCP socket
-    let address = "tcp:host=127.0.0.1,port=4248".to_string();
+    let address = "tcp:host=127.0.0.1,port=0".to_string();
     greet_(&address).await;
 }
 
 async fn greet_(socket_addr: &str) {
-    let mut bus = Bus::for_address(Some(socket_addr)).await.unwrap();
+    let mut bus = Bus::for_config(common::config_for(socket_addr))
+        .await
+        .unwrap();
+    let socket_addr = bus.address().unwrap().to_string();
     let (tx, mut rx) = channel(1);
 
     let handle = tokio::spawn(async move {
@@ -54,8 +59,8 @@ async fn greet_(socket_addr: &str) {
         bus
     });
 
-    let ret = match greet_service(socket_addr).await {
-        Ok(service_conn) => greet_client(socket_addr).await.map(|_| service_conn),
+    let ret = match greet_service(&socket_addr).await {
+        Ok(service_conn) => greet_client(&socket_addr).await.map(|_| service_conn),
         Err(e) => Err(e),
     };
     let _ = tx.send(()).await;
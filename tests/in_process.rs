@@ -0,0 +1,64 @@
+mod common;
+
+use busd::bus::Bus;
+use enumflags2::BitFlag;
+use ntest::timeout;
+use zbus::{
+    connection,
+    fdo::{DBusProxy, RequestNameFlags, RequestNameReply},
+    names::WellKnownName,
+    proxy::CacheProperties,
+};
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+#[timeout(15000)]
+async fn in_process() {
+    busd::tracing_subscriber::init();
+
+    let mut bus = Bus::for_address(Some(&common::unix_address()))
+        .await
+        .unwrap();
+
+    let conn = bus.connect_in_process().await.unwrap();
+    let conn2 = bus.connect_in_process().await.unwrap();
+    assert!(conn.unique_name().is_some());
+    assert!(conn2.unique_name().is_some());
+    assert_ne!(conn.unique_name(), conn2.unique_name());
+
+    common::serve(&mut bus, |address| async move {
+        // In-process peers and peers connected through the socket can see each other.
+        let name: WellKnownName = "org.busd.InProcess".try_into().unwrap();
+        let dbus_proxy = DBusProxy::builder(&conn)
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await
+            .unwrap();
+        let ret = dbus_proxy
+            .request_name(name.clone(), RequestNameFlags::empty())
+            .await
+            .unwrap();
+        assert_eq!(ret, RequestNameReply::PrimaryOwner);
+
+        for other in [
+            conn2,
+            connection::Builder::address(&*address)
+                .unwrap()
+                .build()
+                .await
+                .unwrap(),
+        ] {
+            let dbus_proxy = DBusProxy::builder(&other)
+                .cache_properties(CacheProperties::No)
+                .build()
+                .await
+                .unwrap();
+            let owner = dbus_proxy
+                .get_name_owner(name.clone().into())
+                .await
+                .unwrap();
+            assert_eq!(owner, *conn.unique_name().unwrap());
+        }
+    })
+    .await;
+    bus.cleanup().await.unwrap();
+}
@@ -0,0 +1,128 @@
+use std::{
+    env::temp_dir,
+    fs,
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+    time::Duration,
+};
+
+use nix::{
+    sys::signal::{kill, Signal},
+    unistd::Pid,
+};
+use ntest::timeout;
+use rand::{
+    distr::{Alphanumeric, SampleString},
+    rng,
+};
+use zbus::{connection, fdo::DBusProxy, names::BusName};
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+#[timeout(15000)]
+async fn launch() {
+    busd::tracing_subscriber::init();
+
+    let dir = temp_dir().join(Alphanumeric.sample_string(&mut rng(), 10));
+    fs::create_dir(&dir).unwrap();
+    let socket_path = dir.join("bus");
+    let config_path = dir.join("session.conf");
+    fs::write(
+        &config_path,
+        format!(
+            "<busconfig><listen>unix:path={}</listen></busconfig>",
+            socket_path.display()
+        ),
+    )
+    .unwrap();
+
+    // The output of `--sh-syntax` can be evaluated by a shell.
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(format!(
+            r#"eval "$('{}' launch --sh-syntax --config-file='{}')" && echo "$DBUS_SESSION_BUS_PID" && sh -c 'echo "$DBUS_SESSION_BUS_ADDRESS"'"#,
+            env!("CARGO_BIN_EXE_busd"),
+            config_path.display(),
+        ))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let output = String::from_utf8(output.stdout).unwrap();
+    // Only the address is exported.
+    let (pid, address) = output.trim_end().split_once('\n').unwrap();
+    assert!(address.starts_with(&format!("unix:path={},guid=", socket_path.display())));
+    assert_eq!(bus_pid(address).await, pid.parse::<u32>().unwrap());
+    stop(Pid::from_raw(pid.parse().unwrap()), &socket_path).await;
+
+    // The same with the binary syntax.
+    let output = busd(&config_path, &["--binary-syntax"]);
+    let nul = output.iter().position(|b| *b == 0).unwrap();
+    let address = std::str::from_utf8(&output[..nul]).unwrap();
+    let pid = i32::from_ne_bytes(output[nul + 1..nul + 5].try_into().unwrap());
+    assert_eq!(
+        output.len(),
+        nul + 5 + std::mem::size_of::<std::ffi::c_long>()
+    );
+    assert_eq!(bus_pid(address).await, pid as u32);
+    stop(Pid::from_raw(pid), &socket_path).await;
+
+    // The bus can go away with the session.
+    let mut session = Command::new("sh")
+        .arg("-c")
+        .arg(format!(
+            "'{}' launch --exit-with-session --config-file='{}' && read line",
+            env!("CARGO_BIN_EXE_busd"),
+            config_path.display(),
+        ))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    while !socket_path.exists() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    session.stdin.take().unwrap().write_all(b"bye\n").unwrap();
+    assert!(session.wait().unwrap().success());
+    wait_for_removal(&socket_path).await;
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+fn busd(config_path: &Path, args: &[&str]) -> Vec<u8> {
+    let output = Command::new(env!("CARGO_BIN_EXE_busd"))
+        .arg("launch")
+        .arg("--config-file")
+        .arg(config_path)
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+
+    output.stdout
+}
+
+async fn bus_pid(address: &str) -> u32 {
+    let conn = connection::Builder::address(address)
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+
+    DBusProxy::new(&conn)
+        .await
+        .unwrap()
+        .get_connection_unix_process_id(BusName::try_from("org.freedesktop.DBus").unwrap())
+        .await
+        .unwrap()
+}
+
+async fn stop(pid: Pid, socket_path: &Path) {
+    kill(pid, Signal::SIGTERM).unwrap();
+    wait_for_removal(socket_path).await;
+}
+
+async fn wait_for_removal(socket_path: &Path) {
+    while socket_path.exists() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
@@ -1,3 +1,5 @@
+mod common;
+
 use anyhow::ensure;
 use busd::bus::Bus;
 use futures_util::TryStreamExt;
@@ -19,8 +21,9 @@ use zbus::{
 async fn become_monitor() {
     busd::tracing_subscriber::init();
 
-    let address = "tcp:host=127.0.0.1,port=4242".to_string();
-    let mut bus = Bus::for_address(Some(&address)).await.unwrap();
+    let config = common::config_for("tcp:host=127.0.0.1,port=0");
+    let mut bus = Bus::for_config(config).await.unwrap();
+    let address = bus.address().unwrap().to_string();
     let (tx, rx) = tokio::sync::oneshot::channel();
 
     let handle = tokio::spawn(async move {
@@ -1,3 +1,5 @@
+mod common;
+
 use std::env::temp_dir;
 
 use busd::bus::Bus;
@@ -24,12 +26,20 @@ async fn multi_conenct() {
     multi_conenct_(&address).await;
 
     // TCP socket
-    let address = "tcp:host=127.0.0.1,port=4246".to_string();
+    let address = "tcp:host=127.0.0.1,port=0".to_string();
+    multi_conenct_(&address).await;
+
+    // TCP socket, listening on all IPv4 interfaces
+    let address = "tcp:host=localhost,bind=*,port=0,family=ipv4".to_string();
     multi_conenct_(&address).await;
 }
 
 async fn multi_conenct_(socket_addr: &str) {
-    let mut bus = Bus::for_address(Some(socket_addr)).await.unwrap();
+    let mut bus = Bus::for_config(common::config_for(socket_addr))
+        .await
+        .unwrap();
+    let socket_addr = bus.address().unwrap().to_string();
+    assert!(!socket_addr.contains("port=0,"));
     let (tx, rx) = channel();
 
     let handle = tokio::spawn(async move {
@@ -44,7 +54,7 @@ async fn multi_conenct_(socket_addr: &str) {
         bus
     });
 
-    let ret = multi_clients_connect(socket_addr).await;
+    let ret = multi_clients_connect(&socket_addr).await;
     let _ = tx.send(());
     let bus = handle.await.unwrap();
     bus.cleanup().await.unwrap();
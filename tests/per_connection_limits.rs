@@ -0,0 +1,98 @@
+mod common;
+
+use busd::{
+    bus::{self, Bus},
+    config::{Config, Limits},
+};
+use enumflags2::BitFlag;
+use ntest::timeout;
+use zbus::{
+    fdo::{self, DBusProxy, RequestNameFlags, RequestNameReply},
+    MatchRule,
+};
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+#[timeout(15000)]
+async fn per_connection_limits() {
+    busd::tracing_subscriber::init();
+
+    let config = Config {
+        listen: Some(bus::parse_address(&common::unix_address()).unwrap()),
+        limits: Limits {
+            max_match_rules_per_connection: 2,
+            max_names_per_connection: 2,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let bus = Bus::for_config(config).await.unwrap();
+    let conn = bus.connect_in_process().await.unwrap();
+    let dbus = DBusProxy::new(&conn).await.unwrap();
+
+    // Match rules.
+    let rule = |member: &'static str| {
+        MatchRule::builder()
+            .msg_type(zbus::message::Type::Signal)
+            .member(member)
+            .unwrap()
+            .build()
+    };
+    dbus.add_match_rule(rule("One")).await.unwrap();
+    dbus.add_match_rule(rule("Two")).await.unwrap();
+    // Adding the same rule again doesn't take up more room.
+    dbus.add_match_rule(rule("Two")).await.unwrap();
+    let err = dbus.add_match_rule(rule("Three")).await.unwrap_err();
+    assert!(matches!(err, fdo::Error::LimitsExceeded(_)), "{err:?}");
+    dbus.remove_match_rule(rule("One")).await.unwrap();
+    dbus.add_match_rule(rule("Three")).await.unwrap();
+
+    // Names, queued ones included.
+    let other = bus.connect_in_process().await.unwrap();
+    other.request_name("org.example.Taken").await.unwrap();
+    let reply = dbus
+        .request_name(
+            "org.example.Taken".try_into().unwrap(),
+            RequestNameFlags::empty(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(reply, RequestNameReply::InQueue);
+    let reply = dbus
+        .request_name(
+            "org.example.A".try_into().unwrap(),
+            RequestNameFlags::empty(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(reply, RequestNameReply::PrimaryOwner);
+    // Asking for a name we already own is fine.
+    let reply = dbus
+        .request_name(
+            "org.example.A".try_into().unwrap(),
+            RequestNameFlags::empty(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(reply, RequestNameReply::AlreadyOwner);
+    let err = dbus
+        .request_name(
+            "org.example.B".try_into().unwrap(),
+            RequestNameFlags::empty(),
+        )
+        .await
+        .unwrap_err();
+    assert!(matches!(err, fdo::Error::LimitsExceeded(_)), "{err:?}");
+    dbus.release_name("org.example.Taken".try_into().unwrap())
+        .await
+        .unwrap();
+    let reply = dbus
+        .request_name(
+            "org.example.B".try_into().unwrap(),
+            RequestNameFlags::empty(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(reply, RequestNameReply::PrimaryOwner);
+
+    bus.cleanup().await.unwrap();
+}
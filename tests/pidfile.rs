@@ -0,0 +1,71 @@
+use std::{env::temp_dir, fs, path::Path, process::Command};
+
+use busd::{
+    bus::{self, Bus},
+    config::Config,
+};
+use ntest::timeout;
+use rand::{
+    distr::{Alphanumeric, SampleString},
+    rng,
+};
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+#[timeout(15000)]
+async fn pidfile() {
+    busd::tracing_subscriber::init();
+
+    let dir = temp_dir().join(Alphanumeric.sample_string(&mut rng(), 10));
+    fs::create_dir(&dir).unwrap();
+    let pidfile = dir.join("busd.pid");
+
+    // Our PID is written once we're listening.
+    let bus = Bus::for_config(config(&dir, "bus1", &pidfile))
+        .await
+        .unwrap();
+    assert_eq!(read_pid(&pidfile), std::process::id());
+
+    // Since we're still running, another bus can't use the same file.
+    assert!(Bus::for_config(config(&dir, "bus2", &pidfile))
+        .await
+        .is_err());
+    assert_eq!(read_pid(&pidfile), std::process::id());
+
+    // The file goes away with the bus.
+    bus.cleanup().await.unwrap();
+    assert!(!pidfile.exists());
+
+    // PIDs of processes that are gone are replaced.
+    let mut child = Command::new("true").spawn().unwrap();
+    child.wait().unwrap();
+    fs::write(&pidfile, format!("{}\n", child.id())).unwrap();
+    let bus = Bus::for_config(config(&dir, "bus3", &pidfile))
+        .await
+        .unwrap();
+    assert_eq!(read_pid(&pidfile), std::process::id());
+    bus.cleanup().await.unwrap();
+
+    // Same goes for garbage.
+    fs::write(&pidfile, "garbage").unwrap();
+    let bus = Bus::for_config(config(&dir, "bus4", &pidfile))
+        .await
+        .unwrap();
+    assert_eq!(read_pid(&pidfile), std::process::id());
+    bus.cleanup().await.unwrap();
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+fn config(dir: &Path, socket: &str, pidfile: &Path) -> Config {
+    let address = format!("unix:path={}", dir.join(socket).display());
+
+    Config {
+        listen: Some(bus::parse_address(&address).unwrap()),
+        pidfile: Some(pidfile.to_path_buf()),
+        ..Default::default()
+    }
+}
+
+fn read_pid(pidfile: &Path) -> u32 {
+    fs::read_to_string(pidfile).unwrap().trim().parse().unwrap()
+}
@@ -0,0 +1,106 @@
+use std::{
+    env::temp_dir,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use busd::{bus::Bus, config::Config};
+use nix::unistd::Uid;
+use ntest::timeout;
+use rand::{
+    distr::{Alphanumeric, SampleString},
+    rng,
+};
+use tokio::{select, sync::oneshot::channel};
+use zbus::{connection, fdo::DBusProxy};
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+#[timeout(15000)]
+async fn reload_config() {
+    busd::tracing_subscriber::init();
+
+    let dir = temp_dir().join(Alphanumeric.sample_string(&mut rng(), 10));
+    fs::create_dir(&dir).unwrap();
+    let config_path = dir.join("bus.conf");
+    let address = format!("unix:path={}", dir.join("socket").display());
+    write_config(&config_path, &address, "/services/a", 1000, "");
+
+    let mut bus = Bus::for_config(Config::read_file(&config_path).unwrap())
+        .await
+        .unwrap();
+    bus.set_config_file(&config_path);
+    let (tx, rx) = channel();
+    let handle = tokio::spawn(async move {
+        select! {
+            _ = rx => (),
+            res = bus.run() => panic!("Bus exited unexpectedly: {res:?}"),
+        }
+
+        bus
+    });
+
+    let conn = connection::Builder::address(&*address)
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+    let dbus = DBusProxy::new(&conn).await.unwrap();
+
+    // Deny ourselves. New connections are refused but existing ones are kept.
+    let deny_us = format!(
+        r#"<policy context="default"><deny user="{}"/></policy>"#,
+        Uid::effective()
+    );
+    write_config(&config_path, &address, "/services/b", 2000, &deny_us);
+    dbus.reload_config().await.unwrap();
+    assert!(!can_connect(&address).await);
+    dbus.get_id().await.unwrap();
+
+    // A broken file is reported and the current configuration is kept.
+    fs::write(&config_path, "<busconfig><oops></busconfig>").unwrap();
+    dbus.reload_config().await.unwrap_err();
+    assert!(!can_connect(&address).await);
+    dbus.get_id().await.unwrap();
+
+    tx.send(()).unwrap();
+    let bus = handle.await.unwrap();
+    let config = bus.config();
+    assert_eq!(config.servicedirs, [PathBuf::from("/services/b")]);
+    assert_eq!(config.limits.auth_timeout, Duration::from_millis(2000));
+    assert_eq!(config.policies.len(), 1);
+    bus.reload_config().await.unwrap_err();
+    drop(conn);
+    bus.cleanup().await.unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    // Without a file, there's nothing to reload.
+    let bus = Bus::without_listener().await.unwrap();
+    assert_eq!(
+        bus.reload_config().await.unwrap_err().to_string(),
+        "No server configuration to reload."
+    );
+}
+
+fn write_config(path: &Path, address: &str, servicedir: &str, auth_timeout: u32, policy: &str) {
+    let config = format!(
+        r#"<!DOCTYPE busconfig PUBLIC "-//freedesktop//DTD D-Bus Bus Configuration 1.0//EN"
+    "http://www.freedesktop.org/standards/dbus/1.0/busconfig.dtd">
+<busconfig>
+    <listen>{address}</listen>
+    <servicedir>{servicedir}</servicedir>
+    <limit name="auth_timeout">{auth_timeout}</limit>
+    {policy}
+</busconfig>
+"#
+    );
+    fs::write(path, config).unwrap();
+}
+
+async fn can_connect(address: &str) -> bool {
+    connection::Builder::address(address)
+        .unwrap()
+        .build()
+        .await
+        .is_ok()
+}
@@ -0,0 +1,93 @@
+use std::{
+    env::temp_dir,
+    fs,
+    io::{BufRead, BufReader, Lines, Write},
+    path::Path,
+    process::{ChildStdout, Command, Stdio},
+};
+
+use nix::{
+    sys::signal::{kill, Signal},
+    unistd::Pid,
+};
+use ntest::timeout;
+use rand::{
+    distr::{Alphanumeric, SampleString},
+    rng,
+};
+use zbus::connection;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+#[timeout(15000)]
+async fn run_session() {
+    busd::tracing_subscriber::init();
+
+    let dir = temp_dir().join(Alphanumeric.sample_string(&mut rng(), 10));
+    fs::create_dir(&dir).unwrap();
+    // The `<listen>` element is ignored.
+    let config_path = dir.join("session.conf");
+    fs::write(
+        &config_path,
+        format!(
+            "<busconfig><listen>unix:path={}</listen></busconfig>",
+            dir.join("ignored").display()
+        ),
+    )
+    .unwrap();
+
+    // The program gets the bus and its exit status is ours.
+    let mut busd = run_session_cmd(
+        &config_path,
+        r#"echo "$DBUS_SESSION_BUS_ADDRESS"; read line; exit 3"#,
+    )
+    .stdin(Stdio::piped())
+    .spawn()
+    .unwrap();
+    let mut lines = stdout_lines(busd.stdout.take().unwrap());
+    let address = lines.next().unwrap().unwrap();
+    let socket_path = address
+        .strip_prefix("unix:path=")
+        .and_then(|addr| addr.split(',').next())
+        .map(ToOwned::to_owned)
+        .unwrap();
+    assert!(Path::new(&socket_path).starts_with(temp_dir()));
+    assert!(!dir.join("ignored").exists());
+    let conn = connection::Builder::address(&*address)
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+    conn.request_name("org.example.Session").await.unwrap();
+    drop(conn);
+    busd.stdin.take().unwrap().write_all(b"done\n").unwrap();
+    assert_eq!(busd.wait().unwrap().code(), Some(3));
+    assert!(!Path::new(&socket_path).exists());
+
+    // Signals are passed on to the program.
+    let mut busd = run_session_cmd(
+        &config_path,
+        r#"trap "exit 7" TERM; echo ready; while :; do sleep 0.1; done"#,
+    )
+    .spawn()
+    .unwrap();
+    let mut lines = stdout_lines(busd.stdout.take().unwrap());
+    assert_eq!(lines.next().unwrap().unwrap(), "ready");
+    kill(Pid::from_raw(busd.id() as i32), Signal::SIGTERM).unwrap();
+    assert_eq!(busd.wait().unwrap().code(), Some(7));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+fn run_session_cmd(config_path: &Path, script: &str) -> Command {
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_busd"));
+    cmd.arg("run-session")
+        .arg(format!("--config-file={}", config_path.display()))
+        .args(["--", "sh", "-c", script])
+        .stdout(Stdio::piped());
+
+    cmd
+}
+
+fn stdout_lines(stdout: ChildStdout) -> Lines<BufReader<ChildStdout>> {
+    BufReader::new(stdout).lines()
+}
@@ -0,0 +1,78 @@
+use std::{env::temp_dir, fs, os::unix::net::UnixDatagram, sync::Arc, time::Duration};
+
+use busd::{bus::Bus, sd_notify::Notifier};
+use ntest::timeout;
+use rand::{
+    distr::{Alphanumeric, SampleString},
+    rng,
+};
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+#[timeout(15000)]
+async fn sd_notify() {
+    busd::tracing_subscriber::init();
+
+    // A stand-in for the service manager.
+    let dir = temp_dir().join(Alphanumeric.sample_string(&mut rng(), 10));
+    fs::create_dir(&dir).unwrap();
+    let socket_path = dir.join("notify");
+    let manager = UnixDatagram::bind(&socket_path).unwrap();
+    manager
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let notifier = Arc::new(Notifier::new(&socket_path).unwrap());
+
+    notifier.ready().unwrap();
+    assert_eq!(receive(&manager), "READY=1");
+
+    let bus = Bus::without_listener().await.unwrap();
+    let client = bus.connect_in_process().await.unwrap();
+    client.request_name("org.example.Client").await.unwrap();
+    let watchdog_conn = bus.connect_in_process().await.unwrap();
+    let keep_alive = {
+        let notifier = notifier.clone();
+        tokio::spawn(async move {
+            notifier
+                .keep_alive(&watchdog_conn, Some(Duration::from_millis(100)))
+                .await
+        })
+    };
+
+    // The watchdog connection and the bus itself don't count.
+    assert_eq!(
+        receive(&manager),
+        "STATUS=Connected peers: 1, well-known names: 1\nWATCHDOG=1"
+    );
+
+    // No pings while the bus is stuck.
+    {
+        let _peers = bus.peers().peers_mut().await;
+        // Anything sent before the bus got stuck.
+        manager
+            .set_read_timeout(Some(Duration::from_millis(150)))
+            .unwrap();
+        while manager.recv(&mut [0; 256]).is_ok() {}
+
+        manager
+            .set_read_timeout(Some(Duration::from_millis(500)))
+            .unwrap();
+        manager.recv(&mut [0; 256]).unwrap_err();
+    }
+    manager
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    assert!(receive(&manager).ends_with("\nWATCHDOG=1"));
+
+    keep_alive.abort();
+    notifier.stopping().unwrap();
+    while receive(&manager) != "STOPPING=1" {}
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+fn receive(socket: &UnixDatagram) -> String {
+    let mut buf = [0; 256];
+    let len = socket.recv(&mut buf).unwrap();
+
+    String::from_utf8(buf[..len].to_vec()).unwrap()
+}
@@ -0,0 +1,101 @@
+use std::{
+    env::temp_dir,
+    fs,
+    io::{BufRead, BufReader},
+    path::Path,
+    process::{Command, Stdio},
+    time::Duration,
+};
+
+use busd::bus::Bus;
+use futures_util::stream::StreamExt;
+use nix::{
+    sys::signal::{kill, Signal},
+    unistd::Pid,
+};
+use ntest::timeout;
+use rand::{
+    distr::{Alphanumeric, SampleString},
+    rng,
+};
+use tokio::{select, sync::oneshot::channel};
+use zbus::{connection, Connection, MessageStream};
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+#[timeout(15000)]
+async fn shutdown() {
+    busd::tracing_subscriber::init();
+
+    let dir = temp_dir().join(Alphanumeric.sample_string(&mut rng(), 10));
+    fs::create_dir(&dir).unwrap();
+
+    // Through the API.
+    let socket_path = dir.join("bus1");
+    let address = format!("unix:path={}", socket_path.display());
+    let mut bus = Bus::for_address(Some(&address)).await.unwrap();
+    let (tx, rx) = channel();
+    let handle = tokio::spawn(async move {
+        select! {
+            _ = rx => (),
+            res = bus.run() => panic!("Bus exited unexpectedly: {res:?}"),
+        }
+
+        bus
+    });
+    let conn = connect(&address).await;
+    let stream = MessageStream::from(&conn);
+    tx.send(()).unwrap();
+    let bus = handle.await.unwrap();
+    bus.shutdown(Duration::from_secs(5)).await.unwrap();
+    assert_disconnected(stream).await;
+    assert!(!socket_path.exists());
+    assert!(connection::Builder::address(&*address)
+        .unwrap()
+        .build()
+        .await
+        .is_err());
+
+    // Through SIGTERM, as systemd does it. A configuration file keeps the bus off the defaults.
+    let socket_path = dir.join("bus2");
+    let config_path = dir.join("bus2.conf");
+    fs::write(
+        &config_path,
+        format!(
+            "<busconfig><listen>unix:path={}</listen></busconfig>",
+            socket_path.display()
+        ),
+    )
+    .unwrap();
+    let mut busd = Command::new(env!("CARGO_BIN_EXE_busd"))
+        .arg(format!("--config-file={}", config_path.display()))
+        .arg("--print-address")
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut line = String::new();
+    BufReader::new(busd.stdout.take().unwrap())
+        .read_line(&mut line)
+        .unwrap();
+    let conn = connect(line.trim_end()).await;
+    let stream = MessageStream::from(&conn);
+    kill(Pid::from_raw(busd.id() as i32), Signal::SIGTERM).unwrap();
+    assert_disconnected(stream).await;
+    assert!(busd.wait().unwrap().success());
+    assert!(!Path::new(&socket_path).exists());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+async fn connect(address: &str) -> Connection {
+    connection::Builder::address(address)
+        .unwrap()
+        .build()
+        .await
+        .unwrap()
+}
+
+/// Wait for the bus to close the connection.
+async fn assert_disconnected(mut stream: MessageStream) {
+    // Signals sent before the bus went away still arrive.
+    while let Some(Ok(_)) = stream.next().await {}
+}
@@ -0,0 +1,73 @@
+use std::{env::temp_dir, os::unix::fs::MetadataExt, path::PathBuf, str::FromStr};
+
+use busd::{
+    bus::Bus,
+    config::{BusType, Config},
+};
+use ntest::timeout;
+use rand::{
+    distr::{Alphanumeric, SampleString},
+    rng,
+};
+use zbus::{
+    address::{transport::UnixSocket, Transport},
+    Address,
+};
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+#[timeout(15000)]
+async fn socket_permissions() {
+    busd::tracing_subscriber::init();
+
+    // No configuration means only we can connect.
+    let s = Alphanumeric.sample_string(&mut rng(), 10);
+    let address = format!("unix:path={}", temp_dir().join(s).display());
+    let bus = Bus::for_address(Some(&address)).await.unwrap();
+    assert_eq!(socket_mode(&bus), 0o600);
+    bus.cleanup().await.unwrap();
+
+    // Everyone can connect to a system bus, which we own unless told otherwise.
+    let config = Config {
+        listen: Some(Address::from_str(&address).unwrap()),
+        r#type: Some(BusType::System),
+        user: Some(nix::unistd::Uid::current().to_string()),
+        ..Default::default()
+    };
+    let bus = Bus::for_config(config).await.unwrap();
+    assert_eq!(socket_mode(&bus), 0o666);
+    let metadata = socket_path(&bus).metadata().unwrap();
+    assert_eq!(metadata.uid(), nix::unistd::Uid::current().as_raw());
+    bus.cleanup().await.unwrap();
+
+    // Same goes for sockets in a directory.
+    let config = Config {
+        listen: Some(Address::from_str(&format!("unix:dir={}", temp_dir().display())).unwrap()),
+        r#type: Some(BusType::System),
+        ..Default::default()
+    };
+    let bus = Bus::for_config(config).await.unwrap();
+    assert_eq!(socket_mode(&bus), 0o666);
+    bus.cleanup().await.unwrap();
+
+    // Unknown users are refused.
+    let config = Config {
+        listen: Some(Address::from_str(&address).unwrap()),
+        user: Some("busd-no-such-user".to_string()),
+        ..Default::default()
+    };
+    assert!(Bus::for_config(config).await.is_err());
+}
+
+fn socket_path(bus: &Bus) -> PathBuf {
+    match bus.address().unwrap().transport() {
+        Transport::Unix(unix) => match unix.path() {
+            UnixSocket::File(path) => path.clone(),
+            _ => panic!("expected a socket file"),
+        },
+        _ => panic!("expected a UNIX socket address"),
+    }
+}
+
+fn socket_mode(bus: &Bus) -> u32 {
+    socket_path(bus).metadata().unwrap().mode() & 0o777
+}
@@ -0,0 +1,84 @@
+mod common;
+
+use std::{os::unix::net::UnixListener, path::PathBuf};
+
+use busd::bus::Bus;
+use ntest::timeout;
+use zbus::connection;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+#[timeout(15000)]
+async fn stale_socket() {
+    busd::tracing_subscriber::init();
+
+    stale_socket_is_replaced().await;
+    live_bus_is_not_replaced().await;
+    non_socket_file_is_not_replaced().await;
+}
+
+async fn stale_socket_is_replaced() {
+    let path = common::socket_path();
+    // Leave a socket file behind, as a crashed bus would.
+    drop(UnixListener::bind(&path).unwrap());
+    assert!(path.exists());
+
+    let address = format!("unix:path={}", path.display());
+    let mut bus = Bus::for_address(Some(&address)).await.unwrap();
+    common::serve(&mut bus, |address| async move {
+        let conn = connection::Builder::address(&*address)
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+        assert!(conn.unique_name().is_some());
+    })
+    .await;
+    bus.cleanup().await.unwrap();
+    assert!(!path.exists());
+    assert!(!lock_path(&path).exists());
+}
+
+async fn live_bus_is_not_replaced() {
+    let path = common::socket_path();
+    let address = format!("unix:path={}", path.display());
+    let bus = Bus::for_address(Some(&address)).await.unwrap();
+    assert!(lock_path(&path).exists());
+
+    // The lock file stops us, and it's left alone.
+    assert!(Bus::for_address(Some(&address)).await.is_err());
+    assert!(lock_path(&path).exists());
+    assert!(path.exists());
+
+    bus.cleanup().await.unwrap();
+    assert!(!path.exists());
+
+    // Nor is a socket someone else is listening on, lock file or not.
+    let path = common::socket_path();
+    let listener = UnixListener::bind(&path).unwrap();
+    let address = format!("unix:path={}", path.display());
+    assert!(Bus::for_address(Some(&address)).await.is_err());
+    assert!(path.exists());
+    assert!(!lock_path(&path).exists());
+
+    drop(listener);
+    std::fs::remove_file(&path).unwrap();
+}
+
+async fn non_socket_file_is_not_replaced() {
+    let path = common::socket_path();
+    std::fs::write(&path, "not a socket").unwrap();
+    let address = format!("unix:path={}", path.display());
+    assert!(Bus::for_address(Some(&address)).await.is_err());
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "not a socket");
+    // The lock file we took is gone with us.
+    assert!(!lock_path(&path).exists());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+fn lock_path(socket_path: &std::path::Path) -> PathBuf {
+    let mut path = socket_path.as_os_str().to_owned();
+    path.push(".lock");
+
+    path.into()
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use std::time::Duration;
+
+use busd::bus::Bus;
+use ntest::timeout;
+use tokio::{io::AsyncWriteExt, net::UnixStream, time};
+use zbus::{connection, fdo::DBusProxy, interface, Connection};
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+#[timeout(15000)]
+async fn stalled_client() {
+    busd::tracing_subscriber::init();
+
+    let path = common::socket_path();
+    let address = format!("unix:path={}", path.display());
+    let mut bus = Bus::for_address(Some(&address)).await.unwrap();
+    common::serve(&mut bus, |address| async move {
+        // A client that starts authenticating and then goes silent.
+        let mut stalled = UnixStream::connect(&path).await.unwrap();
+        stalled.write_all(b"\0AUTH EXTERNAL").await.unwrap();
+        time::sleep(Duration::from_millis(100)).await;
+
+        // Well behaved clients should not notice, well before the stalled client times out.
+        time::timeout(Duration::from_secs(2), async {
+            let service = echo_service(&address).await;
+            let client = connect(&address).await;
+
+            let names = DBusProxy::new(&client)
+                .await
+                .unwrap()
+                .list_names()
+                .await
+                .unwrap();
+            assert!(names.iter().any(|name| *name == "org.zbus.Echo"));
+
+            let reply: String = client
+                .call_method(
+                    Some("org.zbus.Echo"),
+                    "/org/zbus/Echo",
+                    Some("org.zbus.Echo1"),
+                    "Echo",
+                    &"hello",
+                )
+                .await
+                .unwrap()
+                .body()
+                .deserialize()
+                .unwrap();
+            assert_eq!(reply, "hello");
+
+            drop(service);
+        })
+        .await
+        .expect("Stalled client held up the bus");
+    })
+    .await;
+    bus.cleanup().await.unwrap();
+}
+
+async fn connect(address: &str) -> Connection {
+    connection::Builder::address(address)
+        .unwrap()
+        .build()
+        .await
+        .unwrap()
+}
+
+async fn echo_service(address: &str) -> Connection {
+    struct Echo;
+
+    #[interface(name = "org.zbus.Echo1")]
+    impl Echo {
+        fn echo(&self, s: String) -> String {
+            s
+        }
+    }
+
+    connection::Builder::address(address)
+        .unwrap()
+        .name("org.zbus.Echo")
+        .unwrap()
+        .serve_at("/org/zbus/Echo", Echo)
+        .unwrap()
+        .build()
+        .await
+        .unwrap()
+}
@@ -0,0 +1,98 @@
+#![cfg(feature = "tracing-subscriber")]
+
+use std::{env::temp_dir, fs, os::unix::net::UnixDatagram, path::Path, time::Duration};
+
+use busd::tracing_subscriber::{SyslogFormat, SyslogLayer};
+use ntest::timeout;
+use rand::{
+    distr::{Alphanumeric, SampleString},
+    rng,
+};
+use tracing::{debug, info, warn};
+use tracing_subscriber::{layer::SubscriberExt, registry};
+
+#[test]
+#[timeout(15000)]
+fn syslog() {
+    let dir = temp_dir().join(Alphanumeric.sample_string(&mut rng(), 10));
+    fs::create_dir(&dir).unwrap();
+    let pid = std::process::id();
+
+    // BSD syslog format, with fields appended to the message.
+    let path = dir.join("log");
+    let messages = log(&path, SyslogFormat::Rfc3164, || {
+        warn!(peer = ":busd.1", member = "Hello", "Something's off");
+        debug!("Not interesting");
+    });
+    // Facility `daemon` (3) and severity `warning` (4).
+    // No timestamp, so the syslog daemon uses the time it gets the message.
+    assert_eq!(
+        messages[0],
+        format!("<28>busd[{pid}]: Something's off peer=:busd.1 member=Hello")
+    );
+    assert_eq!(messages[1], format!("<31>busd[{pid}]: Not interesting"));
+    fs::remove_file(&path).unwrap();
+
+    // Syslog protocol, with fields as structured data.
+    let messages = log(&path, SyslogFormat::Rfc5424, || {
+        info!(name = "org.freedesktop.\"Weird]\"", "Name acquired");
+        info!("No fields");
+    });
+    assert!(messages[0].starts_with("<30>1 "), "{}", messages[0]);
+    assert!(
+        messages[0].ends_with(&format!(
+            "Z - busd {pid} - [busd@32473 name=\"org.freedesktop.\\\"Weird\\]\\\"\"] Name acquired"
+        )),
+        "{}",
+        messages[0]
+    );
+    assert!(
+        messages[1].ends_with(&format!("Z - busd {pid} - - No fields")),
+        "{}",
+        messages[1]
+    );
+    fs::remove_file(&path).unwrap();
+
+    // Journal protocol, with fields as journal fields.
+    let messages = log(&path, SyslogFormat::Journal, || {
+        tracing::error!(peer = ":busd.2", "Multiple\nlines");
+    });
+    let message = &messages[0];
+    for field in [
+        "PRIORITY=3\n",
+        "SYSLOG_FACILITY=3\n",
+        "SYSLOG_IDENTIFIER=busd\n",
+        "TARGET=syslog\n",
+        "PEER=:busd.2\n",
+    ] {
+        assert!(message.contains(field), "{field:?} not in {message:?}");
+    }
+    // Values with line breaks are prefixed with their length instead.
+    let mut multi_line = String::from("MESSAGE\n");
+    multi_line.push_str(std::str::from_utf8(&14u64.to_le_bytes()).unwrap());
+    multi_line.push_str("Multiple\nlines\n");
+    assert!(message.contains(&multi_line), "{message:?}");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Bind a stand-in for the log daemon at `path` and return the messages `f` logs there.
+fn log<F>(path: &Path, format: SyslogFormat, f: F) -> Vec<String>
+where
+    F: FnOnce(),
+{
+    let daemon = UnixDatagram::bind(path).unwrap();
+    daemon
+        .set_read_timeout(Some(Duration::from_millis(100)))
+        .unwrap();
+    let layer = SyslogLayer::with_format(path, format).unwrap();
+    tracing::subscriber::with_default(registry().with(layer), f);
+
+    let mut messages = vec![];
+    let mut buf = [0; 4096];
+    while let Ok(len) = daemon.recv(&mut buf) {
+        messages.push(String::from_utf8(buf[..len].to_vec()).unwrap());
+    }
+
+    messages
+}
@@ -0,0 +1,40 @@
+mod common;
+
+use busd::{bus::Bus, config::Config};
+use ntest::timeout;
+use zbus::connection;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+#[timeout(15000)]
+async fn tcp_allowlist() {
+    busd::tracing_subscriber::init();
+
+    // Clients from anywhere are welcome by default.
+    assert!(can_connect(&[]).await);
+
+    // Local clients are refused if only other networks are allowed.
+    assert!(!can_connect(&["10.0.0.0/8", "fd00::/8"]).await);
+
+    // But not if theirs is allowed too.
+    assert!(can_connect(&["10.0.0.0/8", "127.0.0.0/8"]).await);
+    assert!(can_connect(&["127.0.0.1"]).await);
+}
+
+async fn can_connect(allowed_clients: &[&str]) -> bool {
+    let config = Config {
+        tcp_allowed_clients: allowed_clients.iter().map(|n| n.parse().unwrap()).collect(),
+        ..common::config_for("tcp:host=127.0.0.1,port=0")
+    };
+    let mut bus = Bus::for_config(config).await.unwrap();
+    let connected = common::serve(&mut bus, |address| async move {
+        connection::Builder::address(&*address)
+            .unwrap()
+            .build()
+            .await
+            .is_ok()
+    })
+    .await;
+    bus.cleanup().await.unwrap();
+
+    connected
+}
@@ -0,0 +1,39 @@
+mod common;
+
+use busd::{
+    bus::{self, Bus},
+    config::Config,
+};
+use nix::unistd::{Uid, User};
+use ntest::timeout;
+use zbus::connection;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+#[timeout(15000)]
+async fn user() {
+    busd::tracing_subscriber::init();
+
+    // Running as the configured user already, by name or UID, there's nothing to switch.
+    let us = User::from_uid(Uid::current()).unwrap().unwrap();
+    for user in [us.name.clone(), us.uid.to_string()] {
+        let config = Config {
+            listen: Some(bus::parse_address(&common::unix_address()).unwrap()),
+            user: Some(user),
+            ..Default::default()
+        };
+        let mut bus = Bus::for_config(config).await.unwrap();
+        assert_eq!(Uid::current(), us.uid);
+        assert_eq!(Uid::effective(), us.uid);
+
+        // And we're still the bus owner, so we can connect.
+        common::serve(&mut bus, |address| async move {
+            let _conn = connection::Builder::address(&*address)
+                .unwrap()
+                .build()
+                .await
+                .unwrap();
+        })
+        .await;
+        bus.cleanup().await.unwrap();
+    }
+}